@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+use std::fs;
+
+use chrono::{Local, NaiveDate};
+use serde::Deserialize;
+
+use super::{CommandContext, Reply, Response};
+
+/// How many activities each page of `activities` shows.
+const ACTIVITIES_PER_PAGE: usize = 5;
+
+/// Something members can earn points for, from the activities catalogue.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Activity {
+    /// What members type to claim it
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub category: String,
+    pub points: i64,
+    #[serde(default)]
+    pub gems: i64,
+    /// First day it's on, or always on until `ends` if left out
+    pub starts: Option<NaiveDate>,
+    /// Last day it's on, or on indefinitely if left out
+    pub ends: Option<NaiveDate>
+}
+
+impl Activity {
+    pub fn is_active(&self, today: NaiveDate) -> bool {
+        self.starts.is_none_or(|starts| starts <= today) && self.ends.is_none_or(|ends| today <= ends)
+    }
+
+    /// What claiming it earns, like `10 :star: + 5 :gem:`.
+    pub fn reward(&self) -> String {
+        let mut reward = format!("{} :star:", self.points);
+        if self.gems != 0 {
+            reward.push_str(&format!(" + {} :gem:", self.gems));
+        }
+        reward
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Catalogue {
+    #[serde(default, rename = "activity")]
+    activities: Vec<Activity>
+}
+
+/// Reads every `[[activity]]` in the catalogue at `path`, checking the ids are unique.
+pub fn load_activities(path: &str) -> Result<Vec<Activity>, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+    let catalogue: Catalogue = toml::from_str(&contents).map_err(|err| format!("Could not parse {}: {}", path, err))?;
+
+    let mut ids = HashSet::new();
+    for activity in &catalogue.activities {
+        if !ids.insert(activity.id.as_str()) {
+            return Err(format!("{} lists the activity id {} more than once", path, activity.id));
+        }
+    }
+    Ok(catalogue.activities)
+}
+
+/// Lists the activities that are on today, by category.
+pub async fn activities(ctx: &CommandContext<'_>, _args: &[String]) -> Result<Reply, String> {
+    let activities = load_activities(&ctx.config.activities_path)?;
+    Ok(activity_pages(activities, Local::now().date_naive()))
+}
+
+fn activity_pages(activities: Vec<Activity>, today: NaiveDate) -> Reply {
+    let mut active: Vec<Activity> = activities.into_iter().filter(|activity| activity.is_active(today)).collect();
+    if active.is_empty() {
+        return Response::new("Current Activities", "There's nothing to earn points for right now. Check back soon!").into();
+    }
+    active.sort_by(|a, b| (&a.category, &a.title).cmp(&(&b.category, &b.title)));
+
+    Reply::Pages(active.chunks(ACTIVITIES_PER_PAGE).map(|page| {
+        let mut lines: Vec<String> = Vec::new();
+        let mut category = None;
+        for activity in page {
+            // Every page starts with its category, even if the last page ended in it
+            if category != Some(&activity.category) {
+                lines.push(format!("__{}__", activity.category));
+                category = Some(&activity.category);
+            }
+            lines.push(show_activity(activity));
+        }
+        Response::new("Current Activities", lines.join("\n\n"))
+    }).collect())
+}
+
+fn show_activity(activity: &Activity) -> String {
+    let mut line = format!("**{}** (`{}`): {}\n{}", activity.title, activity.id, activity.reward(), activity.description);
+    if let Some(ends) = activity.ends {
+        line.push_str(&format!("\n_Until {}_", ends.format("%-d %B %Y")));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::fake::{FakeGuild, embed, MEMBER};
+
+    fn activity(id: &str, category: &str, starts: Option<&str>, ends: Option<&str>) -> Activity {
+        Activity {
+            id: id.to_string(),
+            title: format!("{} title", id),
+            description: format!("{} description", id),
+            category: category.to_string(),
+            points: 10,
+            gems: 0,
+            starts: starts.map(|date| date.parse().unwrap()),
+            ends: ends.map(|date| date.parse().unwrap())
+        }
+    }
+
+    fn pages(reply: Reply) -> Vec<Response> {
+        match reply {
+            Reply::Pages(pages) => pages,
+            other => panic!("Expected pages, got {:?}", other)
+        }
+    }
+
+    #[tokio::test]
+    async fn activities_shows_the_configured_catalogue() {
+        let guild = FakeGuild::new();
+        let pages = pages(activities(&guild.as_user(MEMBER), &[]).await.unwrap());
+        assert!(pages[0].description.contains("**Attend an AWS re:Invent screening** (`reinvent`): 10 :star:"));
+    }
+
+    #[test]
+    fn only_active_activities_are_listed_by_category() {
+        let today = "2024-03-10".parse().unwrap();
+        let activities = vec![
+            activity("later", "Events", Some("2024-04-01"), None),
+            activity("over", "Events", None, Some("2024-03-09")),
+            activity("talk", "Events", Some("2024-03-10"), Some("2024-03-10")),
+            Activity { gems: 5, ..activity("cert", "Learning", None, None) }
+        ];
+
+        let pages = pages(activity_pages(activities, today));
+        assert_eq!(pages[0].description, "__Events__\n\n**talk title** (`talk`): 10 :star:\ntalk description\n_Until 10 March 2024_\n\n\
+                                          __Learning__\n\n**cert title** (`cert`): 10 :star: + 5 :gem:\ncert description");
+    }
+
+    #[test]
+    fn nothing_active_says_so() {
+        let activities = vec![activity("over", "Events", None, Some("2024-03-09"))];
+        let response = embed(activity_pages(activities, "2024-03-10".parse().unwrap()));
+        assert!(response.description.starts_with("There's nothing to earn points for"));
+    }
+}
@@ -0,0 +1,227 @@
+use chrono::Local;
+use uuid::Uuid;
+
+use crate::storage::{self, Claim, ClaimStatus};
+use super::activities::{load_activities, Activity};
+use super::tiers::points_changed;
+use super::{CommandContext, Reply, Response, reason_from, show_points};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Submission {
+    Refused(Reply),
+    /// Tell the member `reply`, then post `review` for admins with buttons to approve or reject the claim
+    Submitted { claim_id: String, reply: Response, review: Response }
+}
+
+/// `[activity id] [evidence]`. Records a claim for an activity that's on today, for an admin to review.
+pub async fn claim(ctx: &CommandContext<'_>, args: &[String]) -> Result<Submission, String> {
+    let activity_id = match args.first() {
+        Some(activity_id) => activity_id,
+        None => return Ok(Submission::Refused(ctx.usage("claim [activity id] [evidence, links or attachments]")))
+    };
+    let evidence = args[1..].join(" ");
+    if evidence.is_empty() {
+        return Ok(Submission::Refused(Response::error("No evidence", "Add a description, link or attachment showing you did it").into()));
+    }
+
+    let today = Local::now().date_naive();
+    let activity = match load_activities(&ctx.config.activities_path)?.into_iter().find(|activity| &activity.id == activity_id) {
+        Some(activity) if activity.is_active(today) => activity,
+        _ => return Ok(Submission::Refused(Response::error(
+            "Cannot find activity",
+            format!("There is no current activity `{}`. Use {}activities to see what's on", activity_id, ctx.config.prefix)
+        ).into()))
+    };
+
+    let user_id = ctx.caller.id.to_string();
+    let claims = ctx.storage.get_claims_by_member(&user_id).await?;
+    if claims.iter().any(|claim| claim.activity_id == activity.id && claim.status == ClaimStatus::Pending) {
+        return Ok(Submission::Refused(Response::error(
+            "Already claimed",
+            format!("Your claim for **{}** is still waiting to be reviewed", activity.title)
+        ).into()));
+    }
+
+    let claim = ctx.storage.put_claim(Claim {
+        id: Uuid::new_v4().to_string(),
+        activity_id: activity.id.clone(),
+        discord_id: user_id,
+        evidence,
+        timestamp: storage::now(),
+        status: ClaimStatus::Pending,
+        reviewer_id: None
+    }).await?;
+
+    let reply = Response::new("Claim submitted", format!("Your claim for **{}** ({}) is waiting for an admin to review it", activity.title, activity.reward()));
+    let review = Response::new("Activity claim", format!("<@{}> claimed **{}** for {}\n\n{}", claim.discord_id, activity.title, activity.reward(), claim.evidence))
+        .field("Claim", format!("`{}`", claim.id), true);
+    Ok(Submission::Submitted { claim_id: claim.id, reply, review })
+}
+
+/// `[claim id]`. Awards the member the activity's points and lets them know.
+pub async fn approve(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let id = match args.first() {
+        Some(id) => id,
+        None => return Ok(ctx.usage("approve [claim id]"))
+    };
+    let claim = match ctx.storage.get_claim(id).await? {
+        Some(claim) if claim.status == ClaimStatus::Pending => claim,
+        claim => return Ok(not_pending(id, claim))
+    };
+    // Activities can be taken out of the catalogue while claims for them wait
+    let activity = match find_activity(ctx, &claim.activity_id)? {
+        Some(activity) => activity,
+        None => return Ok(Response::error(
+            "Cannot find activity",
+            format!("`{}` is no longer in the activities catalogue, so there's nothing to award. Reject the claim instead", claim.activity_id)
+        ).into())
+    };
+
+    let member_id = claim.discord_id.parse::<u64>().map_err(|err| err.to_string())?;
+    let entry = ctx.ledger_entry(member_id, activity.points, activity.gems, &format!("Claimed {}", activity.title));
+    let (claim, profile) = match ctx.storage.approve_claim(id, &ctx.caller.id.to_string(), entry).await? {
+        Some(approved) => approved,
+        None => return Ok(not_pending(id, ctx.storage.get_claim(id).await?))
+    };
+    if activity.points != 0 {
+        points_changed(ctx, member_id, profile.points).await;
+    }
+
+    let notice = Response::new("Claim approved", format!("Your claim for **{}** was approved, so you got {}\n\n{}", activity.title, activity.reward(), show_points(&profile)));
+    let mut description = format!("<@{}> got {} for **{}**", claim.discord_id, activity.reward(), activity.title);
+    if ctx.directory.direct_message(member_id, notice).await.is_err() {
+        description.push_str("\nCouldn't DM them about it, so let them know yourself");
+    }
+    Ok(Response::new("Claim approved", description).into())
+}
+
+/// `[claim id] [reason]`. Closes the claim without awarding anything and lets the member know why.
+pub async fn reject(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let id = match args.first() {
+        Some(id) => id,
+        None => return Ok(ctx.usage("reject [claim id] [reason]"))
+    };
+    let claim = match ctx.storage.review_claim(id, ClaimStatus::Rejected, &ctx.caller.id.to_string()).await? {
+        Some(claim) => claim,
+        None => return Ok(not_pending(id, ctx.storage.get_claim(id).await?))
+    };
+    let title = find_activity(ctx, &claim.activity_id)?
+        .map(|activity| activity.title)
+        .unwrap_or_else(|| claim.activity_id.clone());
+    let reason = reason_from(&args[1..], "");
+
+    let member_id = claim.discord_id.parse::<u64>().map_err(|err| err.to_string())?;
+    let mut notice = format!("Your claim for **{}** wasn't approved. Ask an admin if you have questions.", title);
+    let mut description = format!("<@{}>'s claim for **{}** was rejected", claim.discord_id, title);
+    if !reason.is_empty() {
+        notice.push_str(&format!("\n\nReason: {}", reason));
+        description.push_str(&format!(": {}", reason));
+    }
+    if ctx.directory.direct_message(member_id, Response::new("Claim rejected", notice)).await.is_err() {
+        description.push_str("\nCouldn't DM them about it, so let them know yourself");
+    }
+    Ok(Response::new("Claim rejected", description).into())
+}
+
+fn find_activity(ctx: &CommandContext<'_>, activity_id: &str) -> Result<Option<Activity>, String> {
+    Ok(load_activities(&ctx.config.activities_path)?.into_iter().find(|activity| activity.id == activity_id))
+}
+
+/// Why claim `id` can't be reviewed, given what's stored for it.
+fn not_pending(id: &str, claim: Option<Claim>) -> Reply {
+    match claim {
+        Some(claim) => Response::error(
+            "Claim already reviewed",
+            format!("Claim `{}` was already {} by <@{}>", id, claim.status.as_str(), claim.reviewer_id.unwrap_or_default())
+        ),
+        None => Response::error("Cannot find claim", format!("There is no claim `{}`", id))
+    }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ERROR_COLOUR;
+    use crate::commands::fake::{FakeGuild, args, embed, ADMIN, MEMBER};
+    use crate::storage::Storage;
+
+    async fn submit(guild: &FakeGuild, command: &str) -> Claim {
+        match claim(&guild.as_user(MEMBER), &args(command)).await.unwrap() {
+            Submission::Submitted { claim_id, .. } => guild.storage.get_claim(&claim_id).await.unwrap().unwrap(),
+            other => panic!("Expected a submission, got {:?}", other)
+        }
+    }
+
+    #[tokio::test]
+    async fn claims_need_a_current_activity_and_evidence() {
+        let guild = FakeGuild::new();
+        let member = guild.as_user(MEMBER);
+        for command in ["", "reinvent", "nothing https://example.com"] {
+            match claim(&member, &args(command)).await.unwrap() {
+                Submission::Refused(Reply::Embed(response)) => assert_eq!(response.colour, Some(ERROR_COLOUR)),
+                other => panic!("Expected a refusal for {:?}, got {:?}", command, other)
+            }
+        }
+
+        match claim(&member, &args("reinvent \"I was there\" https://example.com/photo.jpg")).await.unwrap() {
+            Submission::Submitted { claim_id, reply, review } => {
+                let claim = guild.storage.get_claim(&claim_id).await.unwrap().unwrap();
+                assert_eq!(claim.evidence, "I was there https://example.com/photo.jpg");
+                assert_eq!(reply.title, "Claim submitted");
+                assert!(review.description.starts_with(&format!("<@{}> claimed **Attend an AWS re:Invent screening** for 10 :star:", MEMBER)));
+            },
+            other => panic!("Expected a submission, got {:?}", other)
+        }
+
+        // Only one claim per activity can wait at a time
+        match claim(&member, &args("reinvent again")).await.unwrap() {
+            Submission::Refused(Reply::Embed(response)) => assert_eq!(response.title, "Already claimed"),
+            other => panic!("Expected a refusal, got {:?}", other)
+        }
+    }
+
+    #[tokio::test]
+    async fn approving_awards_the_activity_once() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        let claim = submit(&guild, "aws-cert https://example.com/cert.pdf").await;
+
+        assert_eq!(approve(&guild.as_user(MEMBER), &args(&claim.id)).await.unwrap(), Reply::Nothing);
+        let response = embed(approve(&admin, &args(&claim.id)).await.unwrap());
+        assert_eq!(response.description, format!("<@{}> got 100 :star: for **Get certified with AWS**", MEMBER));
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().points, 100);
+        let direct_messages = guild.directory.direct_messages.lock().unwrap().clone();
+        assert_eq!((direct_messages[0].0, direct_messages[0].1.title.as_str()), (MEMBER, "Claim approved"));
+
+        let response = embed(reject(&admin, &args(&claim.id)).await.unwrap());
+        assert_eq!(response.description, format!("Claim `{}` was already approved by <@{}>", claim.id, ADMIN));
+        let response = embed(approve(&admin, &args(&claim.id)).await.unwrap());
+        assert_eq!(response.title, "Claim already reviewed");
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().points, 100);
+        let entries = guild.storage.get_entries(&MEMBER.to_string()).await.unwrap();
+        assert_eq!((entries.len(), entries[0].reason.as_str()), (1, "Claimed Get certified with AWS"));
+    }
+
+    #[tokio::test]
+    async fn rejecting_tells_the_member_why() {
+        let guild = FakeGuild::new();
+        let claim = submit(&guild, "bot-issue https://github.com/example/pull/1").await;
+
+        let response = embed(reject(&guild.as_user(ADMIN), &args(&format!("{} Not merged yet", claim.id))).await.unwrap());
+        assert_eq!(response.description, format!("<@{}>'s claim for **Complete an issue on our bots** was rejected: Not merged yet", MEMBER));
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().points, 0);
+        let direct_messages = guild.directory.direct_messages.lock().unwrap().clone();
+        assert!(direct_messages[0].1.description.ends_with("Reason: Not merged yet"));
+
+        // A new claim can go in once the last one is reviewed
+        submit(&guild, "bot-issue https://github.com/example/pull/2").await;
+    }
+}
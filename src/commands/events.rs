@@ -0,0 +1,365 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use chrono::DateTime;
+use uuid::Uuid;
+
+use crate::storage::{self, Attendance, Event, EventOutcome};
+use super::points::{parse_targets, recipients};
+use super::tiers::points_changed;
+use super::{Attachment, CommandContext, Reply, Response, show_points};
+
+/// How many characters generated check-in codes have.
+const CODE_LENGTH: usize = 6;
+
+/// How many of the latest events the attendance report shows; the rest are only in its CSV.
+const REPORT_EVENTS_SHOWN: usize = 10;
+
+/// `[event id] "[name]" [points] [open for, like 90m, 2h or 1d] [code]`. Without a code, one is made up.
+pub async fn addevent(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let (id, name, points, open_for) = match (args.first(), args.get(1), args.get(2).and_then(|points| points.parse::<i64>().ok()),
+                                              args.get(3).and_then(|open_for| parse_duration(open_for))) {
+        (Some(id), Some(name), Some(points), Some(open_for)) => (id, name, points, open_for),
+        _ => return Ok(ctx.usage("addevent [event id] \"[name]\" [points] [open for, like 90m, 2h or 1d] [code]"))
+    };
+    let code = match args.get(4) {
+        Some(code) => code.to_uppercase(),
+        None => Uuid::new_v4().to_simple().to_string()[..CODE_LENGTH].to_uppercase()
+    };
+
+    let now = storage::now();
+    let expires = match now.checked_add(open_for) {
+        Some(expires) => expires,
+        None => return Ok(Response::error("Cannot create event", format!("{} is too long for check-in to be open", args[3])).into())
+    };
+    let event = Event { id: id.clone(), name: name.clone(), code, points, expires, timestamp: now };
+    match ctx.storage.add_event(event.clone()).await? {
+        EventOutcome::Added => {},
+        EventOutcome::IdTaken => return Ok(Response::error("Event already exists", format!("There is already an event `{}`", id)).into()),
+        EventOutcome::CodeInUse(clash) => return Ok(Response::error(
+            "Code in use",
+            format!("`{}` is already the code for **{}** until <t:{}:t>", event.code, clash.name, clash.expires)
+        ).into())
+    }
+    Ok(Response::new("Event created", format!(
+        "**{}** (`{}`)\nMembers can run `{}checkin {}` until <t:{}:t> for {} :star:",
+        event.name, event.id, ctx.config.prefix, event.code, event.expires, event.points
+    )).into())
+}
+
+/// Reads a length of time like `90m`, `2h` or `1d` as seconds, or `None` if it's too long to count.
+fn parse_duration(duration: &str) -> Option<i64> {
+    let unit = match duration.chars().last()? {
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None
+    };
+    match duration[..duration.len() - 1].parse::<i64>().ok()? {
+        amount if amount > 0 => amount.checked_mul(unit),
+        _ => None
+    }
+}
+
+/// `[code]`. Gives the caller an event's points, once, while its code is open.
+pub async fn checkin(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    let code = match args.first() {
+        Some(code) => code.to_uppercase(),
+        None => return Ok(ctx.usage("checkin [code]"))
+    };
+
+    // Codes can be reused once they close, so prefer the event that's open
+    let now = storage::now();
+    let event = ctx.storage.get_events().await?.into_iter()
+        .filter(|event| event.code == code)
+        .max_by_key(|event| (event.expires > now, event.expires));
+    let event = match event {
+        Some(event) if event.expires > now => event,
+        Some(event) => return Ok(Response::error("Check-in closed", format!("Check-in for **{}** closed <t:{}:R>", event.name, event.expires)).into()),
+        None => return Ok(Response::error("Cannot find event", format!("`{}` isn't a check-in code. Check it with whoever is running the event", code)).into())
+    };
+
+    let attendance = Attendance { event_id: event.id.clone(), discord_id: ctx.caller.id.to_string(), timestamp: now };
+    let entry = ctx.ledger_entry(ctx.caller.id, event.points, 0, &format!("Checked in to {}", event.name));
+    match ctx.storage.check_in(attendance, entry).await? {
+        Some(profile) => {
+            points_changed(ctx, ctx.caller.id, profile.points).await;
+            Ok(Response::new("Checked in!", format!("Welcome to **{}**! +{} :star:\n\n{}", event.name, event.points, show_points(&profile))).into())
+        },
+        None => Ok(Response::error("Already checked in", format!("You've already checked in to **{}**", event.name)).into())
+    }
+}
+
+/// `[event id] [@users, @roles or IDs]`. Records members as at an event and gives them its
+/// points, the same as if they'd checked in, for anyone that couldn't.
+pub async fn addattendance(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let targets = if args.is_empty() { None } else { parse_targets(&args[1..]) };
+    let (id, (user_ids, role_ids)) = match (args.first(), targets) {
+        (Some(id), Some(targets)) => (id, targets),
+        _ => return Ok(ctx.usage("addattendance [event id] [@users, @roles or IDs]"))
+    };
+    let event = match ctx.storage.get_event(id).await? {
+        Some(event) => event,
+        None => return Ok(Response::error("Cannot find event", format!("There is no event `{}`", id)).into())
+    };
+
+    let recipients = recipients(ctx, user_ids, &role_ids).await?;
+    if recipients.is_empty() {
+        return Ok(Response::error("No attendance recorded", "Nobody matched those users or roles").into());
+    }
+
+    let reason = format!("Attended {}", event.name);
+    let mut lines: Vec<String> = Vec::new();
+    for (user_id, name) in recipients {
+        let attendance = Attendance { event_id: event.id.clone(), discord_id: user_id.to_string(), timestamp: storage::now() };
+        match ctx.storage.check_in(attendance, ctx.ledger_entry(user_id, event.points, 0, &reason)).await {
+            Ok(Some(profile)) => {
+                points_changed(ctx, user_id, profile.points).await;
+                lines.push(format!("{}: {:+} :star: (now {} :star:)", name, event.points, profile.points));
+            },
+            Ok(None) => lines.push(format!("{}: already attending", name)),
+            Err(err) => {
+                println!("Error: {:?}", err);
+                lines.push(format!("{}: failed, nothing recorded", name));
+            }
+        }
+    }
+    Ok(Response::new("Attendance recorded", format!("**{}**\n\n{}", event.name, lines.join("\n"))).into())
+}
+
+/// `[event id]`. Counts who was at an event, with the full list as a CSV file. Without an
+/// event, reports how many were at every event instead.
+pub async fn attendance(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let id = match args.first() {
+        Some(id) => id,
+        None => return attendance_report(ctx).await
+    };
+    let event = match ctx.storage.get_event(id).await? {
+        Some(event) => event,
+        None => return Ok(Response::error("Cannot find event", format!("There is no event `{}`", id)).into())
+    };
+
+    let mut attendance = ctx.storage.get_attendance(&event.id).await?;
+    let title = format!("Attendance: {}", event.name);
+    if attendance.is_empty() {
+        return Ok(Response::new(title, format!("Nobody is down as attending **{}** yet", event.name)).into());
+    }
+    attendance.sort_by_key(|attendance| attendance.timestamp);
+
+    let mut csv = String::from("discord_id,name,recorded_utc\n");
+    for attendance in &attendance {
+        let name = match attendance.discord_id.parse::<u64>() {
+            Ok(user_id) => ctx.directory.user_name(user_id).await.unwrap_or_default(),
+            Err(_) => String::new()
+        };
+        csv.push_str(&csv_row(&[&attendance.discord_id, &name, &utc(attendance.timestamp, "%Y-%m-%d %H:%M:%S")]));
+    }
+
+    let response = Response::new(title, format!("{} attended **{}**\nCheck-in open until <t:{}:f>", attendance.len(), event.name, event.expires));
+    Ok(Reply::File(response, Attachment { filename: format!("{}-attendance.csv", event.id), contents: csv }))
+}
+
+/// How many were at each event, latest first, with totals for reporting on the club as a whole.
+async fn attendance_report(ctx: &CommandContext<'_>) -> Result<Reply, String> {
+    let mut events = ctx.storage.get_events().await?;
+    if events.is_empty() {
+        return Ok(Response::new("Attendance report", "There are no events yet").into());
+    }
+    events.sort_by_key(|event| Reverse(event.timestamp));
+
+    let mut members: HashSet<String> = HashSet::new();
+    let mut total = 0;
+    let mut lines: Vec<String> = Vec::new();
+    let mut csv = String::from("event_id,name,date_utc,attendees\n");
+    for event in &events {
+        let attendance = ctx.storage.get_attendance(&event.id).await?;
+        let count = attendance.len();
+        total += count;
+        members.extend(attendance.into_iter().map(|attendance| attendance.discord_id));
+        csv.push_str(&csv_row(&[&event.id, &event.name, &utc(event.timestamp, "%Y-%m-%d"), &count.to_string()]));
+        if lines.len() < REPORT_EVENTS_SHOWN {
+            lines.push(format!("<t:{}:d> **{}** (`{}`): {} attended", event.timestamp, event.name, event.id, count));
+        }
+    }
+    if events.len() > REPORT_EVENTS_SHOWN {
+        lines.push(format!("...and {} more in the CSV", events.len() - REPORT_EVENTS_SHOWN));
+    }
+
+    let description = format!("{} events, {} attendances by {} different members\n\n{}", events.len(), total, members.len(), lines.join("\n"));
+    Ok(Reply::File(Response::new("Attendance report", description), Attachment { filename: "attendance-report.csv".to_string(), contents: csv }))
+}
+
+/// A timestamp in UTC, or as it is if it's outside the dates chrono can show.
+fn utc(timestamp: i64, format: &str) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(time) => time.format(format).to_string(),
+        None => timestamp.to_string()
+    }
+}
+
+/// One line of a CSV file, quoting fields that need it.
+fn csv_row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields.iter().map(|field| {
+        if field.contains([',', '"', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }).collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ERROR_COLOUR;
+    use crate::commands::fake::{FakeGuild, args, embed, ADMIN, MEMBER, OTHER_MEMBER};
+    use crate::storage::Storage;
+
+    #[test]
+    fn durations_read_minutes_hours_and_days() {
+        assert_eq!(parse_duration("90m"), Some(90 * 60));
+        assert_eq!(parse_duration("2h"), Some(2 * 60 * 60));
+        assert_eq!(parse_duration("1d"), Some(24 * 60 * 60));
+        assert_eq!(parse_duration("0h"), None);
+        assert_eq!(parse_duration("2"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("999999999999999999d"), None);
+    }
+
+    #[tokio::test]
+    async fn events_cant_stay_open_past_the_end_of_time() {
+        let guild = FakeGuild::new();
+        let response = embed(addevent(&guild.as_user(ADMIN), &args(&format!("rust Rust 15 {}m", i64::MAX / 60))).await.unwrap());
+        assert_eq!((response.title.as_str(), response.colour), ("Cannot create event", Some(ERROR_COLOUR)));
+        assert!(guild.storage.get_events().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn times_outside_what_chrono_shows_stay_as_timestamps() {
+        assert_eq!(utc(0, "%Y-%m-%d %H:%M:%S"), "1970-01-01 00:00:00");
+        assert_eq!(utc(i64::MAX, "%Y-%m-%d"), i64::MAX.to_string());
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_row(&["1", "plain", "a, \"quoted\" name"]), "1,plain,\"a, \"\"quoted\"\" name\"\n");
+    }
+
+    #[tokio::test]
+    async fn members_check_in_once_while_the_code_is_open() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        let member = guild.as_user(MEMBER);
+        assert_eq!(addevent(&member, &args("rust \"Rust workshop\" 15 2h")).await.unwrap(), Reply::Nothing);
+        let response = embed(addevent(&admin, &args("rust \"Rust workshop\" 15 2h ferris")).await.unwrap());
+        assert!(response.description.contains("Members can run `~checkin FERRIS`"));
+        let response = embed(addevent(&admin, &args("rust2 \"Rust again\" 15 2h Ferris")).await.unwrap());
+        assert_eq!(response.title, "Code in use");
+
+        let response = embed(checkin(&member, &args("ferris")).await.unwrap());
+        assert!(response.description.starts_with("Welcome to **Rust workshop**! +15 :star:"));
+        let response = embed(checkin(&member, &args("FERRIS")).await.unwrap());
+        assert_eq!((response.title.as_str(), response.colour), ("Already checked in", Some(ERROR_COLOUR)));
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().points, 15);
+
+        assert_eq!(embed(checkin(&member, &args("nope")).await.unwrap()).title, "Cannot find event");
+    }
+
+    #[tokio::test]
+    async fn closed_codes_are_refused() {
+        let guild = FakeGuild::new();
+        let closed = Event {
+            id: "old".to_string(),
+            name: "Old workshop".to_string(),
+            code: "OLD".to_string(),
+            points: 10,
+            expires: storage::now() - 60,
+            timestamp: storage::now() - 3600
+        };
+        guild.storage.add_event(closed).await.unwrap();
+
+        let response = embed(checkin(&guild.as_user(MEMBER), &args("old")).await.unwrap());
+        assert_eq!(response.title, "Check-in closed");
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().points, 0);
+
+        // Closed codes can go on a new event
+        addevent(&guild.as_user(ADMIN), &args("new \"New workshop\" 10 1h old")).await.unwrap();
+        assert!(embed(checkin(&guild.as_user(MEMBER), &args("old")).await.unwrap()).description.starts_with("Welcome to **New workshop**"));
+    }
+
+    #[tokio::test]
+    async fn attendance_exports_everyone_that_checked_in() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        addevent(&admin, &args("rust \"Rust workshop\" 15 2h ferris")).await.unwrap();
+        assert_eq!(embed(attendance(&admin, &args("rust")).await.unwrap()).description, "Nobody is down as attending **Rust workshop** yet");
+
+        checkin(&guild.as_user(MEMBER), &args("ferris")).await.unwrap();
+        checkin(&guild.as_user(OTHER_MEMBER), &args("ferris")).await.unwrap();
+        match attendance(&admin, &args("rust")).await.unwrap() {
+            Reply::File(response, file) => {
+                assert!(response.description.starts_with("2 attended **Rust workshop**"));
+                assert_eq!(file.filename, "rust-attendance.csv");
+                let lines: Vec<&str> = file.contents.lines().collect();
+                assert_eq!(lines[0], "discord_id,name,recorded_utc");
+                assert_eq!(lines.len(), 3);
+                assert!(lines.iter().any(|line| line.starts_with(&format!("{},member,", MEMBER))));
+            },
+            other => panic!("Expected a file, got {:?}", other)
+        }
+        assert_eq!(attendance(&guild.as_user(MEMBER), &args("rust")).await.unwrap(), Reply::Nothing);
+        assert_eq!(embed(attendance(&admin, &args("nope")).await.unwrap()).title, "Cannot find event");
+    }
+
+    #[tokio::test]
+    async fn admins_can_record_attendance_for_members_that_didnt_check_in() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        addevent(&admin, &args("rust \"Rust workshop\" 15 2h ferris")).await.unwrap();
+        checkin(&guild.as_user(MEMBER), &args("ferris")).await.unwrap();
+
+        assert_eq!(addattendance(&guild.as_user(MEMBER), &args(&format!("rust {}", OTHER_MEMBER))).await.unwrap(), Reply::Nothing);
+        assert_eq!(embed(addattendance(&admin, &args("rust")).await.unwrap()).title, "Usage");
+        let response = embed(addattendance(&admin, &args(&format!("rust <@{}> <@{}>", MEMBER, OTHER_MEMBER))).await.unwrap());
+        assert_eq!(response.description, "**Rust workshop**\n\nmember: already attending\nother: +15 :star: (now 15 :star:)");
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().points, 15);
+        assert_eq!(guild.storage.get_attendance("rust").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn the_report_counts_every_event() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        assert_eq!(embed(attendance(&admin, &[]).await.unwrap()).description, "There are no events yet");
+
+        addevent(&admin, &args("rust \"Rust workshop\" 15 2h ferris")).await.unwrap();
+        addevent(&admin, &args("git \"Git workshop\" 10 2h octocat")).await.unwrap();
+        checkin(&guild.as_user(MEMBER), &args("ferris")).await.unwrap();
+        checkin(&guild.as_user(OTHER_MEMBER), &args("ferris")).await.unwrap();
+        checkin(&guild.as_user(MEMBER), &args("octocat")).await.unwrap();
+
+        match attendance(&admin, &[]).await.unwrap() {
+            Reply::File(response, file) => {
+                assert!(response.description.starts_with("2 events, 3 attendances by 2 different members"));
+                assert_eq!(file.filename, "attendance-report.csv");
+                let mut lines: Vec<&str> = file.contents.lines().skip(1).map(|line| line.rsplit(',').next().unwrap()).collect();
+                lines.sort();
+                assert_eq!(lines, vec!["1", "2"]);
+            },
+            other => panic!("Expected a file, got {:?}", other)
+        }
+    }
+}
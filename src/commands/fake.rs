@@ -0,0 +1,146 @@
+//! A guild that only exists in memory, so commands can be run in tests without Discord.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serenity::async_trait;
+
+use crate::config::Config;
+use crate::storage::{MemoryStorage, Storage};
+use super::{Caller, CommandContext, Directory, Reply, Response};
+
+pub const ADMIN: u64 = 100000000000000001;
+pub const MEMBER: u64 = 100000000000000002;
+pub const OTHER_MEMBER: u64 = 100000000000000003;
+pub const COMMITTEE_ROLE: u64 = 200000000000000001;
+
+/// Members of the fake guild, with their names and roles, and the DMs and channel messages sent.
+#[derive(Default)]
+pub struct FakeDirectory {
+    members: Mutex<HashMap<u64, (String, Vec<u64>)>>,
+    pub direct_messages: Mutex<Vec<(u64, Response)>>,
+    /// Messages sent to channels, with the channel's ID
+    pub posts: Mutex<Vec<(u64, Response)>>
+}
+
+impl FakeDirectory {
+    pub fn with_member(mut self, user_id: u64, name: &str, roles: &[u64]) -> FakeDirectory {
+        self.members.get_mut().unwrap().insert(user_id, (name.to_string(), roles.to_vec()));
+        self
+    }
+
+    pub fn roles(&self, user_id: u64) -> Vec<u64> {
+        self.members.lock().unwrap().get(&user_id).map(|(_, roles)| roles.clone()).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl Directory for FakeDirectory {
+    async fn user_name(&self, user_id: u64) -> Option<String> {
+        self.members.lock().unwrap().get(&user_id).map(|(name, _)| name.clone())
+    }
+
+    async fn members_with_roles(&self, role_ids: &[u64]) -> Result<Vec<(u64, String)>, String> {
+        Ok(self.members.lock().unwrap().iter()
+            .filter(|(_, (_, roles))| roles.iter().any(|role| role_ids.contains(role)))
+            .map(|(user_id, (name, _))| (*user_id, name.clone()))
+            .collect())
+    }
+
+    async fn direct_message(&self, user_id: u64, message: Response) -> Result<(), String> {
+        if !self.members.lock().unwrap().contains_key(&user_id) {
+            return Err("Cannot send messages to this user".to_string());
+        }
+        self.direct_messages.lock().unwrap().push((user_id, message));
+        Ok(())
+    }
+
+    async fn post(&self, channel_id: u64, message: Response) -> Result<(), String> {
+        self.posts.lock().unwrap().push((channel_id, message));
+        Ok(())
+    }
+
+    async fn member_roles(&self, user_id: u64) -> Result<Vec<u64>, String> {
+        match self.members.lock().unwrap().get(&user_id) {
+            Some((_, roles)) => Ok(roles.clone()),
+            None => Err("Unknown member".to_string())
+        }
+    }
+
+    async fn add_role(&self, user_id: u64, role_id: u64) -> Result<(), String> {
+        match self.members.lock().unwrap().get_mut(&user_id) {
+            Some((_, roles)) => {
+                roles.push(role_id);
+                Ok(())
+            },
+            None => Err("Unknown member".to_string())
+        }
+    }
+
+    async fn remove_role(&self, user_id: u64, role_id: u64) -> Result<(), String> {
+        match self.members.lock().unwrap().get_mut(&user_id) {
+            Some((_, roles)) => {
+                roles.retain(|role| *role != role_id);
+                Ok(())
+            },
+            None => Err("Unknown member".to_string())
+        }
+    }
+}
+
+/// Fresh storage and a guild with an admin and two members, one of them on the committee.
+/// The storage is in memory unless another backend is given with `with_storage`.
+pub struct FakeGuild<S: Storage = MemoryStorage> {
+    pub storage: S,
+    pub directory: FakeDirectory,
+    pub config: Config
+}
+
+impl FakeGuild {
+    pub fn new() -> FakeGuild {
+        Default::default()
+    }
+}
+
+impl Default for FakeGuild {
+    fn default() -> FakeGuild {
+        FakeGuild::with_storage(MemoryStorage::new())
+    }
+}
+
+impl<S: Storage> FakeGuild<S> {
+    pub fn with_storage(storage: S) -> FakeGuild<S> {
+        FakeGuild {
+            storage,
+            directory: FakeDirectory::default()
+                .with_member(ADMIN, "admin", &[])
+                .with_member(MEMBER, "member", &[COMMITTEE_ROLE])
+                .with_member(OTHER_MEMBER, "other", &[]),
+            config: Config::default()
+        }
+    }
+
+    /// Runs commands as `user_id`; only `ADMIN` is an admin.
+    pub fn as_user(&self, user_id: u64) -> CommandContext<'_> {
+        let name = self.directory.members.lock().unwrap().get(&user_id).map(|(name, _)| name.clone()).unwrap_or_default();
+        CommandContext {
+            storage: &self.storage,
+            directory: &self.directory,
+            config: &self.config,
+            caller: Caller { id: user_id, name, is_admin: user_id == ADMIN, message_id: "1".to_string() }
+        }
+    }
+}
+
+/// Splits arguments the way the serenity commands do.
+pub fn args(args: &str) -> Vec<String> {
+    shell_words::split(args).unwrap()
+}
+
+/// The embed a command replied with, failing the test if it replied with anything else.
+pub fn embed(reply: Reply) -> Response {
+    match reply {
+        Reply::Embed(response) => response,
+        other => panic!("Expected an embed, got {:?}", other)
+    }
+}
@@ -0,0 +1,164 @@
+//! What every command does, without any Discord in it. Each command takes the
+//! caller, its arguments and somewhere to look members up, and returns the embed
+//! to reply with; the serenity commands in `main.rs` just feed messages in and
+//! send the replies out.
+
+use serenity::async_trait;
+use serenity::utils::parse_username;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::storage::{self, Storage, Profile, LedgerEntry};
+
+pub mod activities;
+pub mod claims;
+pub mod events;
+pub mod orders;
+pub mod points;
+pub mod store;
+pub mod tiers;
+
+// Public so the integration tests can run commands against every backend as well
+#[doc(hidden)]
+pub mod fake;
+
+/// Error embeds are always this colour, whatever the configured one is.
+pub const ERROR_COLOUR: u64 = 0xe74c3c;
+
+/// An embed to reply with. Without a colour the configured one is used.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub title: String,
+    pub description: String,
+    pub colour: Option<u64>,
+    pub fields: Vec<Field>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub value: String,
+    pub inline: bool
+}
+
+impl Response {
+    pub fn new(title: impl Into<String>, description: impl Into<String>) -> Response {
+        Response { title: title.into(), description: description.into(), colour: None, fields: Vec::new() }
+    }
+
+    pub fn error(title: impl Into<String>, description: impl Into<String>) -> Response {
+        Response { colour: Some(ERROR_COLOUR), ..Response::new(title, description) }
+    }
+
+    pub fn field(mut self, name: impl Into<String>, value: impl Into<String>, inline: bool) -> Response {
+        self.fields.push(Field { name: name.into(), value: value.into(), inline });
+        self
+    }
+}
+
+/// What a command wants sent back.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reply {
+    /// Say nothing, e.g. when someone who isn't an admin tries an admin command
+    Nothing,
+    Embed(Response),
+    /// Too long for one embed; the caller can flip between these
+    Pages(Vec<Response>),
+    /// An embed with a file to download, like a CSV export
+    File(Response, Attachment)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment {
+    pub filename: String,
+    pub contents: String
+}
+
+impl From<Response> for Reply {
+    fn from(response: Response) -> Reply {
+        Reply::Embed(response)
+    }
+}
+
+/// Whoever sent the command.
+#[derive(Clone, Debug)]
+pub struct Caller {
+    pub id: u64,
+    pub name: String,
+    pub is_admin: bool,
+    /// The message or slash command the command came from, recorded against any balance changes
+    pub message_id: String
+}
+
+/// Looks members up in the guild a command came from, and gets in touch with them.
+#[async_trait]
+pub trait Directory: Send + Sync {
+    /// A user's name, or `None` if there's no such user.
+    async fn user_name(&self, user_id: u64) -> Option<String>;
+    /// Every member holding any of `role_ids`, with their names.
+    async fn members_with_roles(&self, role_ids: &[u64]) -> Result<Vec<(u64, String)>, String>;
+    /// Sends a user an embed in their DMs. Fails if they don't accept DMs from the bot.
+    async fn direct_message(&self, user_id: u64, message: Response) -> Result<(), String>;
+    /// Sends an embed to a channel, like an announcement.
+    async fn post(&self, channel_id: u64, message: Response) -> Result<(), String>;
+    /// The roles a member holds. Fails if they aren't in the guild.
+    async fn member_roles(&self, user_id: u64) -> Result<Vec<u64>, String>;
+    async fn add_role(&self, user_id: u64, role_id: u64) -> Result<(), String>;
+    async fn remove_role(&self, user_id: u64, role_id: u64) -> Result<(), String>;
+}
+
+/// Everything a command can use.
+pub struct CommandContext<'a> {
+    pub storage: &'a dyn Storage,
+    pub directory: &'a dyn Directory,
+    pub config: &'a Config,
+    pub caller: Caller
+}
+
+impl<'a> CommandContext<'a> {
+    /// Tells the caller how to use a command, given the command and its arguments.
+    pub fn usage(&self, usage: &str) -> Reply {
+        Response::error("Usage", format!("{}{}", self.config.prefix, usage)).into()
+    }
+
+    /// Builds a ledger entry for a balance change made by the caller.
+    pub fn ledger_entry(&self, user_id: u64, points: i64, credits: i64, reason: &str) -> LedgerEntry {
+        LedgerEntry {
+            id: Uuid::new_v4().to_string(),
+            actor_id: self.caller.id.to_string(),
+            discord_id: user_id.to_string(),
+            points,
+            credits,
+            reason: reason.to_string(),
+            timestamp: storage::now(),
+            message_id: self.caller.message_id.clone()
+        }
+    }
+}
+
+/// Reads a user mention or a bare user ID.
+pub fn parse_user(arg: &str) -> Option<u64> {
+    parse_username(arg).or_else(|| arg.parse::<u64>().ok())
+}
+
+pub fn show_points(profile: &Profile) -> String {
+    format!("{} :star:\n{} :gem:", profile.points, profile.credits)
+}
+
+/// Joins any arguments left after a command's own into a reason, or uses `default`.
+pub fn reason_from(args: &[String], default: &str) -> String {
+    if args.is_empty() {
+        default.to_string()
+    }
+    else {
+        args.join(" ")
+    }
+}
+
+/// A command's arguments, without the command itself. Quotes group words together
+/// where they're balanced; otherwise the message is just split on whitespace.
+pub fn split_args(content: &str) -> Vec<String> {
+    let words = shell_words::split(content)
+        .unwrap_or_else(|_| content.split_whitespace().map(str::to_string).collect());
+    words.into_iter().skip(1).collect()
+}
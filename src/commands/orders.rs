@@ -0,0 +1,296 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::storage::{Purchase, PurchaseStatus, RefundOutcome};
+use super::{CommandContext, Reply, Response};
+
+/// How many orders each page of `orders` shows.
+const ORDERS_PAGE_SIZE: usize = 10;
+
+/// Lists purchases that haven't been handed over yet.
+pub async fn orders(ctx: &CommandContext<'_>, _args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let pending = ctx.storage.get_purchases_by_status(PurchaseStatus::Pending).await?;
+    if pending.is_empty() {
+        return Ok(Response::new("Pending orders", "There are no orders waiting to be fulfilled").into());
+    }
+
+    let names: HashMap<String, String> = ctx.storage.get_store().await?.into_iter()
+        .map(|product| (product.key, product.name))
+        .collect();
+    let lines: Vec<String> = pending.iter().map(|purchase| {
+        // Deleted products are still owed to whoever bought them
+        let name = names.get(&purchase.product_key).unwrap_or(&purchase.product_key);
+        format!("`{}` **{}** for <@{}>", purchase.id, name, purchase.discord_id)
+    }).collect();
+    Ok(Reply::Pages(lines.chunks(ORDERS_PAGE_SIZE).map(|page| Response::new("Pending orders", page.join("\n"))).collect()))
+}
+
+/// Lists everything the caller has bought, newest first.
+pub async fn mypurchases(ctx: &CommandContext<'_>, _args: &[String]) -> Result<Reply, String> {
+    let mut purchases = ctx.storage.get_purchases_by_member(&ctx.caller.id.to_string()).await?;
+    if purchases.is_empty() {
+        return Ok(Response::new("Your purchases", "You haven't bought anything yet").into());
+    }
+    purchases.sort_by_key(|purchase| Reverse(purchase.timestamp));
+
+    let names: HashMap<String, String> = ctx.storage.get_store().await?.into_iter()
+        .map(|product| (product.key, product.name))
+        .collect();
+    let lines: Vec<String> = purchases.iter().map(|purchase| {
+        let name = names.get(&purchase.product_key).unwrap_or(&purchase.product_key);
+        let line = format!("**{}** for {} :gem: ({})", name, purchase.price, purchase.status.as_str());
+        // Purchases from before timestamps were recorded have no date to show
+        match purchase.timestamp {
+            0 => line,
+            timestamp => format!("<t:{}:d> {}", timestamp, line)
+        }
+    }).collect();
+    Ok(Reply::Pages(lines.chunks(ORDERS_PAGE_SIZE).map(|page| Response::new("Your purchases", page.join("\n"))).collect()))
+}
+
+/// `[purchase id]`. Closes a pending order once the product has been handed over.
+pub async fn fulfil(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let id = match args.first() {
+        Some(id) => id,
+        None => return Ok(ctx.usage("fulfil [purchase id]"))
+    };
+
+    let purchase = match ctx.storage.update_purchase_status(id, PurchaseStatus::Pending, PurchaseStatus::Fulfilled).await? {
+        Some(purchase) => purchase,
+        None => return Ok(match ctx.storage.get_purchase(id).await? {
+            Some(purchase) => already_closed(&purchase),
+            None => Response::error("Cannot find order", format!("There is no order `{}`", id))
+        }.into())
+    };
+
+    let name = product_name(ctx, &purchase).await?;
+    let notice = match &purchase.code {
+        Some(code) => Response::new("Order fulfilled", format!("Your code for {} is `{}`", name, code)),
+        None => Response::new("Order fulfilled", format!("Your {} is ready. Enjoy!", name))
+    };
+    let buyer_id = purchase.discord_id.parse::<u64>().map_err(|err| err.to_string())?;
+    let mut description = format!("Order `{}` for {} by <@{}> is now fulfilled", purchase.id, name, purchase.discord_id);
+    if ctx.directory.direct_message(buyer_id, notice).await.is_err() {
+        description.push_str("\nCouldn't DM them about it, so let them know yourself");
+    }
+    Ok(Response::new("Order updated", description).into())
+}
+
+/// `[purchase id]`. Calls off a pending order, giving the buyer their gems back.
+pub async fn cancel(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    pay_back(ctx, args, PurchaseStatus::Cancelled).await
+}
+
+/// `[purchase id]`. Gives the buyer their gems back for an order, even once it's been fulfilled.
+pub async fn refund(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    pay_back(ctx, args, PurchaseStatus::Refunded).await
+}
+
+/// Cancels or refunds an order, depending on `to`, and lets the buyer know.
+async fn pay_back(ctx: &CommandContext<'_>, args: &[String], to: PurchaseStatus) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let (command, verb) = match to {
+        PurchaseStatus::Cancelled => ("cancel", "Cancelled"),
+        _ => ("refund", "Refunded")
+    };
+    let id = match args.first() {
+        Some(id) => id,
+        None => return Ok(ctx.usage(&format!("{} [purchase id]", command)))
+    };
+    let purchase = match ctx.storage.get_purchase(id).await? {
+        Some(purchase) => purchase,
+        None => return Ok(Response::error("Cannot find order", format!("There is no order `{}`", id)).into())
+    };
+    let buyer_id = purchase.discord_id.parse::<u64>().map_err(|err| err.to_string())?;
+    let name = product_name(ctx, &purchase).await?;
+
+    let entry = ctx.ledger_entry(buyer_id, 0, 0, &format!("{} {}", verb, purchase.product_key));
+    let outcome = match to {
+        PurchaseStatus::Cancelled => ctx.storage.cancel(id, entry).await?,
+        _ => ctx.storage.refund(id, entry).await?
+    };
+    let (purchase, profile) = match outcome {
+        RefundOutcome::Refunded { purchase, profile } => (purchase, profile),
+        RefundOutcome::AlreadyClosed(purchase) => return Ok(already_closed(&purchase).into()),
+        RefundOutcome::PriceUnknown(_) => {
+            let description = format!("Order `{}` is from before prices were recorded, so what was paid isn't known. \
+                                       Give back what they paid with `givegems` and close it with `fulfil`", id);
+            return Ok(Response::error("Price unknown", description).into());
+        },
+        RefundOutcome::NoSuchPurchase => return Ok(Response::error("Cannot find order", format!("There is no order `{}`", id)).into())
+    };
+
+    let title = format!("Order {}", to.as_str());
+    let notice = Response::new(&title, format!("Your order for {} was {}, so you got {} :gem: back\nYou now have {} :gem:", name, to.as_str(), purchase.price, profile.credits));
+    let mut description = format!("{} order `{}` for {}: <@{}> got {} :gem: back", verb, purchase.id, name, purchase.discord_id, purchase.price);
+    if ctx.directory.direct_message(buyer_id, notice).await.is_err() {
+        description.push_str("\nCouldn't DM them about it, so let them know yourself");
+    }
+    Ok(Response::new(title, description).into())
+}
+
+fn already_closed(purchase: &Purchase) -> Response {
+    Response::error("Order already closed", format!("Order `{}` is already {}", purchase.id, purchase.status.as_str()))
+}
+
+async fn product_name(ctx: &CommandContext<'_>, purchase: &Purchase) -> Result<String, String> {
+    Ok(ctx.storage.get_product(&purchase.product_key).await?
+        .map(|product| product.name)
+        .unwrap_or_else(|| purchase.product_key.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ERROR_COLOUR;
+    use crate::commands::fake::{FakeGuild, args, embed, ADMIN, MEMBER};
+    use crate::commands::points::{getpoints, givegems};
+    use crate::commands::store::{addproduct, buy, store_pages};
+    use crate::storage::Storage;
+
+    /// Has `MEMBER` buy a hoodie, returning the order's id.
+    async fn order_hoodie(guild: &FakeGuild) -> String {
+        let admin = guild.as_user(ADMIN);
+        addproduct(&admin, &args("hoodie \"Club hoodie\" Warm 50 5")).await.unwrap();
+        givegems(&admin, &args(&format!("{} 50", MEMBER))).await.unwrap();
+        buy(&guild.as_user(MEMBER), &args("hoodie")).await.unwrap();
+        guild.storage.get_purchases_by_status(PurchaseStatus::Pending).await.unwrap()[0].id.clone()
+    }
+
+    #[tokio::test]
+    async fn orders_lists_pending_purchases() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        assert_eq!(embed(orders(&admin, &[]).await.unwrap()).description, "There are no orders waiting to be fulfilled");
+
+        let id = order_hoodie(&guild).await;
+        match orders(&admin, &[]).await.unwrap() {
+            Reply::Pages(pages) => assert_eq!(pages[0].description, format!("`{}` **Club hoodie** for <@{}>", id, MEMBER)),
+            other => panic!("Expected pages, got {:?}", other)
+        }
+        assert_eq!(orders(&guild.as_user(MEMBER), &[]).await.unwrap(), Reply::Nothing);
+    }
+
+    #[tokio::test]
+    async fn fulfilling_closes_the_order_and_tells_the_buyer() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        let id = order_hoodie(&guild).await;
+
+        let response = embed(fulfil(&admin, &args(&id)).await.unwrap());
+        assert_eq!(response.description, format!("Order `{}` for Club hoodie by <@{}> is now fulfilled", id, MEMBER));
+        assert!(guild.storage.get_purchases_by_status(PurchaseStatus::Pending).await.unwrap().is_empty());
+        let direct_messages = guild.directory.direct_messages.lock().unwrap().clone();
+        assert_eq!(direct_messages.len(), 1);
+        assert_eq!((direct_messages[0].0, direct_messages[0].1.title.as_str()), (MEMBER, "Order fulfilled"));
+
+        // It can't be settled twice
+        let response = embed(cancel(&admin, &args(&id)).await.unwrap());
+        assert_eq!((response.description, response.colour), (format!("Order `{}` is already fulfilled", id), Some(ERROR_COLOUR)));
+    }
+
+    #[tokio::test]
+    async fn cancelling_needs_a_real_order() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        assert_eq!(embed(cancel(&admin, &[]).await.unwrap()).title, "Usage");
+        assert_eq!(embed(cancel(&admin, &args("nope")).await.unwrap()).title, "Cannot find order");
+
+        let id = order_hoodie(&guild).await;
+        assert_eq!(cancel(&guild.as_user(MEMBER), &args(&id)).await.unwrap(), Reply::Nothing);
+        embed(cancel(&admin, &args(&id)).await.unwrap());
+        assert_eq!(guild.storage.get_purchase(&id).await.unwrap().unwrap().status, PurchaseStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn cancelling_returns_gems_and_stock_once() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        let id = order_hoodie(&guild).await;
+        assert_eq!(embed(getpoints(&guild.as_user(MEMBER), &[]).await.unwrap()).description, "0 :star:\n0 :gem:");
+
+        let response = embed(cancel(&admin, &args(&id)).await.unwrap());
+        assert_eq!(response.description, format!("Cancelled order `{}` for Club hoodie: <@{}> got 50 :gem: back", id, MEMBER));
+        assert_eq!(embed(getpoints(&guild.as_user(MEMBER), &[]).await.unwrap()).description, "0 :star:\n50 :gem:");
+        assert!(store_pages(&admin).await.unwrap()[0].response.description.contains("5 left"));
+        let entries = guild.storage.get_entries(&MEMBER.to_string()).await.unwrap();
+        assert_eq!((entries.last().unwrap().credits, entries.last().unwrap().reason.as_str()), (50, "Cancelled hoodie"));
+        let direct_messages = guild.directory.direct_messages.lock().unwrap().clone();
+        assert_eq!(direct_messages[0].1.description, "Your order for Club hoodie was cancelled, so you got 50 :gem: back\nYou now have 50 :gem:");
+
+        // Cancelled orders were already paid back, so they can't be refunded too
+        let response = embed(refund(&admin, &args(&id)).await.unwrap());
+        assert_eq!((response.description, response.colour), (format!("Order `{}` is already cancelled", id), Some(ERROR_COLOUR)));
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().credits, 50);
+    }
+
+    #[tokio::test]
+    async fn refunds_return_gems_and_stock_once() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        let id = order_hoodie(&guild).await;
+        fulfil(&admin, &args(&id)).await.unwrap();
+
+        let response = embed(refund(&admin, &args(&id)).await.unwrap());
+        assert_eq!(response.description, format!("Refunded order `{}` for Club hoodie: <@{}> got 50 :gem: back", id, MEMBER));
+        assert_eq!(embed(getpoints(&guild.as_user(MEMBER), &[]).await.unwrap()).description, "0 :star:\n50 :gem:");
+        assert!(store_pages(&admin).await.unwrap()[0].response.description.contains("5 left"));
+        let entries = guild.storage.get_entries(&MEMBER.to_string()).await.unwrap();
+        assert_eq!((entries.last().unwrap().credits, entries.last().unwrap().reason.as_str()), (50, "Refunded hoodie"));
+
+        let response = embed(refund(&admin, &args(&id)).await.unwrap());
+        assert_eq!((response.description, response.colour), (format!("Order `{}` is already refunded", id), Some(ERROR_COLOUR)));
+        assert_eq!(embed(refund(&admin, &args("nope")).await.unwrap()).title, "Cannot find order");
+        assert_eq!(refund(&guild.as_user(MEMBER), &args(&id)).await.unwrap(), Reply::Nothing);
+    }
+
+    #[tokio::test]
+    async fn orders_from_before_prices_cant_be_paid_back() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        addproduct(&admin, &args("hoodie \"Club hoodie\" Warm 50 5")).await.unwrap();
+        guild.storage.put_legacy_purchase(Purchase {
+            id: "old".to_string(),
+            product_key: "hoodie".to_string(),
+            discord_id: MEMBER.to_string(),
+            price: 0,
+            timestamp: 0,
+            status: PurchaseStatus::Pending,
+            code: None
+        });
+
+        let response = embed(refund(&admin, &args("old")).await.unwrap());
+        assert_eq!((response.title.as_str(), response.colour), ("Price unknown", Some(ERROR_COLOUR)));
+        assert_eq!(embed(cancel(&admin, &args("old")).await.unwrap()).title, "Price unknown");
+        assert_eq!(guild.storage.get_purchase("old").await.unwrap().unwrap().status, PurchaseStatus::Pending);
+        assert!(store_pages(&admin).await.unwrap()[0].response.description.contains("5 left"));
+        assert!(guild.storage.get_entries(&MEMBER.to_string()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn mypurchases_lists_only_the_callers_orders() {
+        let guild = FakeGuild::new();
+        let member = guild.as_user(MEMBER);
+        assert_eq!(embed(mypurchases(&member, &[]).await.unwrap()).description, "You haven't bought anything yet");
+
+        let id = order_hoodie(&guild).await;
+        fulfil(&guild.as_user(ADMIN), &args(&id)).await.unwrap();
+        let timestamp = guild.storage.get_purchase(&id).await.unwrap().unwrap().timestamp;
+        match mypurchases(&member, &[]).await.unwrap() {
+            Reply::Pages(pages) => assert_eq!(pages[0].description, format!("<t:{}:d> **Club hoodie** for 50 :gem: (fulfilled)", timestamp)),
+            other => panic!("Expected pages, got {:?}", other)
+        }
+        assert_eq!(embed(mypurchases(&guild.as_user(ADMIN), &[]).await.unwrap()).description, "You haven't bought anything yet");
+    }
+}
@@ -0,0 +1,525 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use serenity::utils::{parse_role, parse_username};
+
+use crate::storage::{self, Profile, LedgerEntry, TransferOutcome};
+use super::tiers::points_changed;
+use super::{CommandContext, Reply, Response, parse_user, reason_from, show_points};
+
+const HISTORY_PAGE_SIZE: usize = 10;
+
+const LEADERBOARD_DEFAULT_TOP: usize = 10;
+const LEADERBOARD_MAX_TOP: usize = 100;
+const LEADERBOARD_PAGE_SIZE: usize = 10;
+
+/// Discord IDs are at least this long, which is how we tell them apart from amounts.
+const SNOWFLAKE_MIN_LENGTH: usize = 15;
+
+/// Works out which member a command is about: a mention or user ID given as the
+/// first argument, or otherwise the caller. `None` if there's no such user.
+async fn target_user(ctx: &CommandContext<'_>, args: &[String]) -> Option<(u64, String)> {
+    match args.first() {
+        Some(arg) => {
+            let user_id = parse_user(arg)?;
+            let name = ctx.directory.user_name(user_id).await?;
+            Some((user_id, name))
+        },
+        None => Some((ctx.caller.id, ctx.caller.name.clone()))
+    }
+}
+
+pub async fn getpoints(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    let (user_id, name) = match target_user(ctx, args).await {
+        Some(user) => user,
+        None => return Ok(Response::error("Point Count", "Could not find user").into())
+    };
+
+    let profile = ctx.storage.get_profile(&user_id.to_string()).await?;
+    Ok(Response::new(format!("{}'s points", name), show_points(&profile)).into())
+}
+
+pub async fn history(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    let (user_id, name) = match target_user(ctx, args).await {
+        Some(user) => user,
+        None => return Ok(Response::error("History", "Could not find user").into())
+    };
+
+    let entries = ctx.storage.get_entries(&user_id.to_string()).await?;
+    let title = format!("{}'s history", name);
+    if entries.is_empty() {
+        return Ok(Response::new(title, format!("{} has no points or gems history yet", name)).into());
+    }
+
+    let lines: Vec<String> = entries.iter().rev().map(show_entry).collect();
+    Ok(Reply::Pages(lines.chunks(HISTORY_PAGE_SIZE).map(|page| Response::new(&title, page.join("\n"))).collect()))
+}
+
+fn show_entry(entry: &LedgerEntry) -> String {
+    let mut changes: Vec<String> = Vec::new();
+    if entry.points != 0 {
+        changes.push(format!("{:+} :star:", entry.points));
+    }
+    if entry.credits != 0 {
+        changes.push(format!("{:+} :gem:", entry.credits));
+    }
+    // Like givepoints with an amount of 0, or buying something free
+    let changes = if changes.is_empty() { "no change".to_string() } else { format!("**{}**", changes.join(" ")) };
+    // Opening balances weren't given by anyone
+    if entry.actor_id.is_empty() {
+        return format!("<t:{}:d> {} {}", entry.timestamp, changes, entry.reason);
+    }
+    format!("<t:{}:d> {} {} (by <@{}>)", entry.timestamp, changes, entry.reason, entry.actor_id)
+}
+
+/// `[top N] [gems]`
+pub async fn leaderboard(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    let by_gems = args.iter().any(|arg| arg == "gems");
+    let top = args.iter()
+        .filter_map(|arg| arg.parse::<usize>().ok())
+        .next()
+        .unwrap_or(LEADERBOARD_DEFAULT_TOP)
+        .min(LEADERBOARD_MAX_TOP);
+    let title = if by_gems { "Leaderboard (:gem:)" } else { "Leaderboard (:star:)" };
+
+    let ranked = rank_profiles(ctx.storage.get_profiles().await?, by_gems);
+    if ranked.is_empty() {
+        return Ok(Response::new(title, "Nobody is on the leaderboard yet").into());
+    }
+
+    let caller_id = ctx.caller.id.to_string();
+    let mut lines: Vec<String> = Vec::new();
+    for (rank, user_id, profile) in ranked.iter().take(top) {
+        let name = match user_id.parse::<u64>() {
+            Ok(id) => ctx.directory.user_name(id).await.unwrap_or_else(|| user_id.to_string()),
+            Err(_) => user_id.to_string()
+        };
+        let line = format!("#{} {} - {} :star: {} :gem:", rank, name, profile.points, profile.credits);
+        if *user_id == caller_id {
+            lines.push(format!("**{}**", line));
+        } else {
+            lines.push(line);
+        }
+    }
+
+    let caller_rank = match ranked.iter().find(|(_, user_id, _)| *user_id == caller_id) {
+        Some((rank, _, _)) => format!("You are ranked #{} of {}", rank, ranked.len()),
+        None => "You aren't on the leaderboard yet".to_string()
+    };
+    Ok(Reply::Pages(lines.chunks(LEADERBOARD_PAGE_SIZE).map(|page| {
+        Response::new(title, format!("{}\n\n{}", page.join("\n"), caller_rank))
+    }).collect()))
+}
+
+/// Orders members with a non-zero score from highest to lowest, by points or by gems.
+/// Members on the same score share a rank.
+pub fn rank_profiles(profiles: Vec<(String, Profile)>, by_gems: bool) -> Vec<(usize, String, Profile)> {
+    let score = |profile: &Profile| if by_gems { profile.credits } else { profile.points };
+    let mut profiles: Vec<(String, Profile)> = profiles.into_iter().filter(|(_, profile)| score(profile) > 0).collect();
+    profiles.sort_by_key(|(_, profile)| Reverse(score(profile)));
+
+    let mut ranked: Vec<(usize, String, Profile)> = Vec::new();
+    for (index, (user_id, profile)) in profiles.into_iter().enumerate() {
+        let rank = match ranked.last() {
+            Some((last_rank, _, last)) if score(last) == score(&profile) => *last_rank,
+            _ => index + 1
+        };
+        ranked.push((rank, user_id, profile));
+    }
+    ranked
+}
+
+/// Who to award, how much and why, as given to givepoints.
+#[derive(Debug, PartialEq)]
+pub struct Award {
+    pub user_ids: Vec<u64>,
+    pub role_ids: Vec<u64>,
+    pub amount: i64,
+    pub reason: Option<String>
+}
+
+/// Reads a user mention, role mention or user ID (or several, comma separated) into
+/// `user_ids` or `role_ids`. `Some(false)` if `arg` isn't one, `None` if it looked like IDs but wasn't.
+fn parse_target(arg: &str, user_ids: &mut Vec<u64>, role_ids: &mut Vec<u64>) -> Option<bool> {
+    if let Some(role_id) = parse_role(arg) {
+        role_ids.push(role_id);
+    }
+    else if let Some(user_id) = parse_username(arg) {
+        user_ids.push(user_id);
+    }
+    else if arg.contains(',') || arg.len() >= SNOWFLAKE_MIN_LENGTH {
+        for id in arg.split(',').filter(|id| !id.is_empty()) {
+            user_ids.push(id.parse::<u64>().ok()?);
+        }
+    }
+    else {
+        return Some(false);
+    }
+    Some(true)
+}
+
+/// Parses `[targets...]` as user and role IDs, where every argument is a target the way
+/// givepoints takes them. `None` if there are none or any argument isn't one.
+pub fn parse_targets(args: &[String]) -> Option<(Vec<u64>, Vec<u64>)> {
+    let mut user_ids: Vec<u64> = Vec::new();
+    let mut role_ids: Vec<u64> = Vec::new();
+    for arg in args {
+        if !parse_target(arg, &mut user_ids, &mut role_ids)? {
+            return None;
+        }
+    }
+    if user_ids.is_empty() && role_ids.is_empty() {
+        return None;
+    }
+    Some((user_ids, role_ids))
+}
+
+/// Parses `[targets...] amount [reason]`, where targets are user mentions, role mentions
+/// or user IDs (which may be comma separated). Anything after the amount is the reason.
+pub fn parse_award(args: &[String]) -> Option<Award> {
+    let mut user_ids: Vec<u64> = Vec::new();
+    let mut role_ids: Vec<u64> = Vec::new();
+    let mut args = args.iter();
+
+    let amount = loop {
+        let arg = args.next()?;
+        if !parse_target(arg, &mut user_ids, &mut role_ids)? {
+            break arg.parse::<i64>().ok()?;
+        }
+    };
+
+    if user_ids.is_empty() && role_ids.is_empty() {
+        return None;
+    }
+
+    let reason: Vec<&str> = args.map(String::as_str).collect();
+    let reason = if reason.is_empty() { None } else { Some(reason.join(" ")) };
+    Some(Award { user_ids, role_ids, amount, reason })
+}
+
+/// Everyone given as users or holding any of the roles, along with their names for summaries.
+pub async fn recipients(ctx: &CommandContext<'_>, user_ids: Vec<u64>, role_ids: &[u64]) -> Result<BTreeMap<u64, String>, String> {
+    let mut recipients: BTreeMap<u64, String> = BTreeMap::new();
+    for user_id in user_ids {
+        let name = ctx.directory.user_name(user_id).await.unwrap_or_else(|| user_id.to_string());
+        recipients.insert(user_id, name);
+    }
+    if !role_ids.is_empty() {
+        recipients.extend(ctx.directory.members_with_roles(role_ids).await?);
+    }
+    Ok(recipients)
+}
+
+/// Gives a member points, and any gems, on the caller's behalf. Everything that
+/// awards points goes through here, returning the member's profile afterwards.
+pub async fn award_points(ctx: &CommandContext<'_>, user_id: u64, points: i64, credits: i64, reason: &str) -> Result<Profile, String> {
+    let profile = ctx.storage.record_entry(ctx.ledger_entry(user_id, points, credits, reason)).await?;
+    if points != 0 {
+        points_changed(ctx, user_id, profile.points).await;
+    }
+    Ok(profile)
+}
+
+/// `[@users, @roles or IDs] [amount] "[reason]"`
+pub async fn givepoints(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let award = match parse_award(args) {
+        Some(award) => award,
+        None => return Ok(ctx.usage("givepoints [@users, @roles or IDs] [amount] \"[reason]\""))
+    };
+
+    let recipients = recipients(ctx, award.user_ids, &award.role_ids).await?;
+    if recipients.is_empty() {
+        return Ok(Response::error("No points given", "Nobody matched those users or roles").into());
+    }
+
+    let reason = award.reason.unwrap_or_else(|| "Given points".to_string());
+    let mut lines: Vec<String> = Vec::new();
+    for (user_id, name) in recipients {
+        match award_points(ctx, user_id, award.amount, 0, &reason).await {
+            Ok(new_profile) => lines.push(format!("{}: {:+} :star: (now {} :star:)", name, award.amount, new_profile.points)),
+            Err(err) => {
+                println!("Error: {:?}", err);
+                lines.push(format!("{}: failed, nothing given", name));
+            }
+        }
+    }
+
+    Ok(Response::new("Given points!", format!("{}\n\n{}", reason, lines.join("\n"))).into())
+}
+
+/// `[user] [amount] [reason]`
+pub async fn givegems(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let (user_id, amt) = match (args.first().and_then(|arg| parse_user(arg)), args.get(1).and_then(|amt| amt.parse::<i64>().ok())) {
+        (Some(user_id), Some(amt)) => (user_id, amt),
+        _ => return Ok(ctx.usage("givegems [user] [amount] [reason]"))
+    };
+    let reason = reason_from(&args[2..], "Given gems");
+
+    let new_profile = ctx.storage.record_entry(ctx.ledger_entry(user_id, 0, amt, &reason)).await?;
+    Ok(Response::new("Given gems!", show_points(&new_profile)).into())
+}
+
+/// A transfer that has been checked and is waiting for the sender to confirm it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingTransfer {
+    pub recipient_id: u64,
+    pub recipient_name: String,
+    pub amount: i64
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transfer {
+    Refused(Reply),
+    /// Ask the sender `prompt`, then call `confirm_transfer` if they agree
+    NeedsConfirmation { transfer: PendingTransfer, prompt: Response }
+}
+
+/// `[@user] [amount]`. Checks the transfer can go ahead; nothing moves until it is confirmed.
+pub async fn transfer(ctx: &CommandContext<'_>, args: &[String]) -> Result<Transfer, String> {
+    let (recipient_id, amount) = match (args.first().and_then(parse_username), args.get(1).and_then(|amt| amt.parse::<i64>().ok())) {
+        (Some(recipient_id), Some(amount)) => (recipient_id, amount),
+        _ => return Ok(Transfer::Refused(ctx.usage("transfer [@user] [amount]")))
+    };
+
+    if recipient_id == ctx.caller.id {
+        return Ok(Transfer::Refused(Response::error("Transfer failed", "You can't send gems to yourself").into()));
+    }
+    if amount <= 0 {
+        return Ok(Transfer::Refused(Response::error("Transfer failed", "You can only send a positive amount of :gem:").into()));
+    }
+    let recipient_name = match ctx.directory.user_name(recipient_id).await {
+        Some(name) => name,
+        None => return Ok(Transfer::Refused(Response::error("Transfer failed", "Could not find user").into()))
+    };
+
+    let profile = ctx.storage.get_profile(&ctx.caller.id.to_string()).await?;
+    if profile.credits < amount {
+        return Ok(Transfer::Refused(Response::error("Transfer failed", format!("You only have {} :gem:", profile.credits)).into()));
+    }
+
+    let prompt = Response::new("Confirm transfer", format!("Send {} :gem: to {}?", amount, recipient_name));
+    Ok(Transfer::NeedsConfirmation { transfer: PendingTransfer { recipient_id, recipient_name, amount }, prompt })
+}
+
+/// Moves the gems once the sender has confirmed.
+pub async fn confirm_transfer(ctx: &CommandContext<'_>, transfer: PendingTransfer) -> Result<Reply, String> {
+    let debit = ctx.ledger_entry(ctx.caller.id, 0, -transfer.amount, &format!("Sent to {}", transfer.recipient_name));
+    let credit = ctx.ledger_entry(transfer.recipient_id, 0, transfer.amount, &format!("Sent by {}", ctx.caller.name));
+    match ctx.storage.transfer(debit, credit).await? {
+        TransferOutcome::Transferred(new_profile) => {
+            let description = format!("Sent {} :gem: to {}\nYou have {} :gem: left", transfer.amount, transfer.recipient_name, new_profile.credits);
+            Ok(Response::new("Transfer successful", description).into())
+        },
+        TransferOutcome::InsufficientCredits(credits) => {
+            Ok(Response::error("Transfer failed", format!("You only have {} :gem:", credits)).into())
+        }
+    }
+}
+
+pub fn cancelled_transfer() -> Reply {
+    Response::new("Transfer cancelled", "No gems were sent").into()
+}
+
+/// `[user]`. Checks a member's balance against what their ledger adds up to.
+pub async fn audit(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let user_id = match args.first().and_then(|arg| parse_user(arg)) {
+        Some(user_id) => user_id.to_string(),
+        None => return Ok(ctx.usage("audit [user]"))
+    };
+
+    let profile = ctx.storage.get_profile(&user_id).await?;
+    let entries = ctx.storage.get_entries(&user_id).await?;
+    let expected = storage::balance(&entries);
+
+    let response = if expected.points == profile.points && expected.credits == profile.credits {
+        Response::new("Balance matches the ledger", format!("<@{}>", user_id))
+    } else {
+        Response::error("Balance does not match the ledger!", format!("<@{}>", user_id))
+    };
+    Ok(response
+        .field("Current balance", show_points(&profile), true)
+        .field(format!("From {} ledger entries", entries.len()), show_points(&expected), true)
+        .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ERROR_COLOUR;
+    use crate::storage::Storage;
+    use crate::commands::fake::{FakeGuild, args, embed, ADMIN, MEMBER, OTHER_MEMBER, COMMITTEE_ROLE};
+
+    #[tokio::test]
+    async fn getpoints_shows_the_callers_balance() {
+        let guild = FakeGuild::new();
+        let response = embed(getpoints(&guild.as_user(MEMBER), &[]).await.unwrap());
+        assert_eq!(response.title, "member's points");
+        assert_eq!(response.description, "0 :star:\n0 :gem:");
+    }
+
+    #[tokio::test]
+    async fn getpoints_on_an_unknown_user_is_an_error() {
+        let guild = FakeGuild::new();
+        let response = embed(getpoints(&guild.as_user(MEMBER), &args("<@123456789012345678>")).await.unwrap());
+        assert_eq!(response.description, "Could not find user");
+        assert_eq!(response.colour, Some(ERROR_COLOUR));
+    }
+
+    #[tokio::test]
+    async fn givepoints_is_ignored_for_members() {
+        let guild = FakeGuild::new();
+        let reply = givepoints(&guild.as_user(MEMBER), &args(&format!("<@{}> 10", MEMBER))).await.unwrap();
+        assert_eq!(reply, Reply::Nothing);
+        assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().points, 0);
+    }
+
+    #[tokio::test]
+    async fn givepoints_awards_users_and_roles_once_each() {
+        let guild = FakeGuild::new();
+        let command = format!("<@{}> {},{} <@&{}> 10 \"Ran a workshop\"", MEMBER, MEMBER, OTHER_MEMBER, COMMITTEE_ROLE);
+        let response = embed(givepoints(&guild.as_user(ADMIN), &args(&command)).await.unwrap());
+        assert_eq!(response.title, "Given points!");
+        assert_eq!(response.description, "Ran a workshop\n\nmember: +10 :star: (now 10 :star:)\nother: +10 :star: (now 10 :star:)");
+        assert_eq!(guild.storage.get_entries(&MEMBER.to_string()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn givepoints_without_an_amount_shows_usage() {
+        let guild = FakeGuild::new();
+        let response = embed(givepoints(&guild.as_user(ADMIN), &args(&format!("<@{}>", MEMBER))).await.unwrap());
+        assert_eq!(response.title, "Usage");
+        assert!(response.description.starts_with("~givepoints"));
+    }
+
+    #[test]
+    fn awards_parse_targets_amount_and_reason() {
+        let award = parse_award(&args("<@&200000000000000001> 100000000000000002 -5 Late")).unwrap();
+        assert_eq!(award, Award { user_ids: vec![MEMBER], role_ids: vec![COMMITTEE_ROLE], amount: -5, reason: Some("Late".to_string()) });
+        assert_eq!(parse_award(&args("10")), None);
+        assert_eq!(parse_award(&args("<@100000000000000002> ten")), None);
+    }
+
+    #[tokio::test]
+    async fn givegems_adds_gems() {
+        let guild = FakeGuild::new();
+        let response = embed(givegems(&guild.as_user(ADMIN), &args(&format!("<@{}> 5 Hackathon", MEMBER))).await.unwrap());
+        assert_eq!(response.description, "0 :star:\n5 :gem:");
+        assert_eq!(guild.storage.get_entries(&MEMBER.to_string()).await.unwrap()[0].reason, "Hackathon");
+    }
+
+    #[tokio::test]
+    async fn leaderboard_ranks_ties_together_and_bolds_the_caller() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        givepoints(&admin, &args(&format!("{},{} 10", MEMBER, OTHER_MEMBER))).await.unwrap();
+        givepoints(&admin, &args(&format!("{} 5", ADMIN))).await.unwrap();
+
+        let pages = match leaderboard(&guild.as_user(OTHER_MEMBER), &[]).await.unwrap() {
+            Reply::Pages(pages) => pages,
+            other => panic!("Expected pages, got {:?}", other)
+        };
+        let lines: Vec<&str> = pages[0].description.lines().collect();
+        assert_eq!(lines[2], "#3 admin - 5 :star: 0 :gem:");
+        assert!(lines.contains(&"**#1 other - 10 :star: 0 :gem:**"));
+        assert_eq!(lines.last(), Some(&"You are ranked #1 of 3"));
+    }
+
+    #[tokio::test]
+    async fn history_lists_newest_first() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        givepoints(&admin, &args(&format!("{} 10 First", MEMBER))).await.unwrap();
+        givegems(&admin, &args(&format!("{} 3 Second", MEMBER))).await.unwrap();
+
+        match history(&guild.as_user(MEMBER), &[]).await.unwrap() {
+            Reply::Pages(pages) => {
+                let lines: Vec<&str> = pages[0].description.lines().collect();
+                assert!(lines[0].contains("**+3 :gem:** Second"));
+                assert!(lines[1].contains("**+10 :star:** First"));
+            },
+            other => panic!("Expected pages, got {:?}", other)
+        }
+    }
+
+    #[tokio::test]
+    async fn history_shows_entries_that_changed_nothing() {
+        let guild = FakeGuild::new();
+        givepoints(&guild.as_user(ADMIN), &args(&format!("{} 0 Nothing", MEMBER))).await.unwrap();
+
+        match history(&guild.as_user(MEMBER), &[]).await.unwrap() {
+            Reply::Pages(pages) => assert!(pages[0].description.contains(":d> no change Nothing (by <@")),
+            other => panic!("Expected pages, got {:?}", other)
+        }
+    }
+
+    #[tokio::test]
+    async fn transfers_are_checked_before_confirming() {
+        let guild = FakeGuild::new();
+        givegems(&guild.as_user(ADMIN), &args(&format!("{} 20", MEMBER))).await.unwrap();
+        let member = guild.as_user(MEMBER);
+
+        let refusal = |transfer: Transfer| match transfer {
+            Transfer::Refused(reply) => embed(reply).description,
+            other => panic!("Expected a refusal, got {:?}", other)
+        };
+        assert_eq!(refusal(transfer(&member, &args(&format!("<@{}> 5", MEMBER))).await.unwrap()), "You can't send gems to yourself");
+        assert_eq!(refusal(transfer(&member, &args(&format!("<@{}> 0", OTHER_MEMBER))).await.unwrap()), "You can only send a positive amount of :gem:");
+        assert_eq!(refusal(transfer(&member, &args(&format!("<@{}> 50", OTHER_MEMBER))).await.unwrap()), "You only have 20 :gem:");
+
+        let pending = match transfer(&member, &args(&format!("<@{}> 15", OTHER_MEMBER))).await.unwrap() {
+            Transfer::NeedsConfirmation { transfer, prompt } => {
+                assert_eq!(prompt.description, "Send 15 :gem: to other?");
+                transfer
+            },
+            other => panic!("Expected a confirmation, got {:?}", other)
+        };
+        let response = embed(confirm_transfer(&member, pending).await.unwrap());
+        assert_eq!(response.description, "Sent 15 :gem: to other\nYou have 5 :gem: left");
+        assert_eq!(guild.storage.get_profile(&OTHER_MEMBER.to_string()).await.unwrap().credits, 15);
+    }
+
+    #[tokio::test]
+    async fn audit_compares_balance_with_ledger() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        givepoints(&admin, &args(&format!("{} 10", MEMBER))).await.unwrap();
+
+        let response = embed(audit(&admin, &args(&format!("<@{}>", MEMBER))).await.unwrap());
+        assert_eq!(response.title, "Balance matches the ledger");
+        assert_eq!(response.fields[1].name, "From 1 ledger entries");
+        assert_eq!(audit(&guild.as_user(MEMBER), &args(&MEMBER.to_string())).await.unwrap(), Reply::Nothing);
+    }
+
+    #[tokio::test]
+    async fn balances_from_before_the_ledger_get_an_opening_entry() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        guild.storage.put_legacy_profile(&MEMBER.to_string(), Profile { points: 40, credits: 12 });
+        let command = args(&format!("<@{}>", MEMBER));
+        assert_eq!(embed(audit(&admin, &command).await.unwrap()).title, "Balance does not match the ledger!");
+
+        assert_eq!(storage::open_ledgers(&guild.storage).await.unwrap(), 1);
+        assert_eq!(storage::open_ledgers(&guild.storage).await.unwrap(), 0);
+        givepoints(&admin, &args(&format!("{} 10", MEMBER))).await.unwrap();
+
+        let response = embed(audit(&admin, &command).await.unwrap());
+        assert_eq!(response.title, "Balance matches the ledger");
+        assert_eq!(response.fields[1].value, "50 :star:\n12 :gem:");
+        match history(&guild.as_user(MEMBER), &[]).await.unwrap() {
+            Reply::Pages(pages) => assert!(pages[0].description.ends_with("**+40 :star: +12 :gem:** Opening balance, from before the ledger")),
+            other => panic!("Expected pages, got {:?}", other)
+        }
+    }
+}
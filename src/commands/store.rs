@@ -0,0 +1,507 @@
+use uuid::Uuid;
+
+use crate::storage::{self, Storage, Product, ProductChanges, Profile, Purchase, PurchaseOutcome, PurchaseStatus};
+use super::{CommandContext, Reply, Response};
+
+/// Discord won't show more suggestions than this.
+const MAX_CHOICES: usize = 25;
+
+/// How many products each page of the store shows.
+pub const PRODUCTS_PER_PAGE: usize = 5;
+
+/// Discord rejects menu options and suggestions with text longer than this.
+const MAX_LABEL: usize = 100;
+
+pub fn show_product(product: &Product) -> String {
+    let mut details = format!("{} :gem:, {}", product.price, show_stock(product));
+    if product.member_limit > 0 {
+        details.push_str(&format!(", {} per member", product.member_limit));
+    }
+    format!("`{}`: **{}** ({})\n{}", product.key, product.name, details, product.description)
+}
+
+/// How many are left, for anywhere a product is listed.
+pub fn show_stock(product: &Product) -> String {
+    if product.unlimited {
+        "unlimited".to_string()
+    } else {
+        format!("{} left", product.quantity)
+    }
+}
+
+/// `text` cut down to fit in a menu option or suggestion, marking where it was cut.
+pub fn label(text: &str) -> String {
+    if text.chars().count() <= MAX_LABEL {
+        text.to_string()
+    } else {
+        format!("{}…", text.chars().take(MAX_LABEL - 1).collect::<String>())
+    }
+}
+
+/// One page of the store, with the products on it so one can be picked.
+#[derive(Clone, Debug)]
+pub struct StorePage {
+    pub response: Response,
+    pub products: Vec<Product>
+}
+
+/// Everything for sale ordered by key, a page at a time. Empty if there's nothing.
+pub async fn store_pages(ctx: &CommandContext<'_>) -> Result<Vec<StorePage>, String> {
+    let mut products = ctx.storage.get_store().await?;
+    products.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(products.chunks(PRODUCTS_PER_PAGE).map(|page| {
+        let product_lines: Vec<String> = page.iter().map(show_product).collect();
+        StorePage { response: Response::new("Store: ", product_lines.join("\n\n")), products: page.to_vec() }
+    }).collect())
+}
+
+pub fn empty_store() -> Reply {
+    Response::new("Store: ", "The store is empty right now").into()
+}
+
+/// `[key] "[name]" "[description]" [price] [quantity] [field]=[value]...`, with the same fields as `editproduct`
+pub async fn addproduct(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let usage = "addproduct [key] \"[name]\" \"[description]\" [price] [quantity] [field]=[value]...";
+    if args.len() < 5 {
+        return Ok(ctx.usage(usage));
+    }
+    let product = match parse_product(args) {
+        Ok(product) => product,
+        Err(problem) => return Ok(Response::error("Cannot add product", format!("{}\nUsage: {}{}", problem, ctx.config.prefix, usage)).into())
+    };
+    // Replacing it would leave its stock out of step with its codes
+    if let Some(existing) = ctx.storage.get_product(&product.key).await?.filter(|existing| existing.digital) {
+        return Ok(Response::error("Cannot add product", format!("{} is sold as codes, so it can't be replaced; change it with editproduct instead", existing.name)).into());
+    }
+
+    let product = ctx.storage.put_product(product).await?;
+    Ok(Response::new("Added Product", show_product(&product)).into())
+}
+
+/// `[key]`
+pub async fn delproduct(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let key = match args.first() {
+        Some(key) => key,
+        None => return Ok(ctx.usage("delproduct [key]"))
+    };
+
+    ctx.storage.delete_product(key).await?;
+    Ok(Response::new("Deleted Product", format!("Deleted product {}", key)).into())
+}
+
+/// `[key] [field]=[value]...` where each field is `name`, `description`, `price`, `quantity`,
+/// `limit` (how many each member may buy, 0 for any number) or `unlimited` (`yes` or `no`)
+pub async fn editproduct(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let usage = "editproduct [key] [field]=[value]...";
+    let (key, changes) = match args.split_first() {
+        Some((key, changes)) if !changes.is_empty() => (key, changes),
+        _ => return Ok(ctx.usage(usage))
+    };
+    let changes = match parse_changes(changes) {
+        Ok(changes) => changes,
+        Err(problem) => return Ok(Response::error("Cannot edit product", format!("{}\nUsage: {}{}", problem, ctx.config.prefix, usage)).into())
+    };
+    // A digital product's stock is however many codes are left
+    if changes.quantity.is_some() || changes.unlimited.is_some() {
+        if let Some(product) = ctx.storage.get_product(key).await?.filter(|product| product.digital) {
+            return Ok(Response::error("Cannot edit product", format!("{} is sold as codes, so its stock changes with addcodes instead", product.name)).into());
+        }
+    }
+
+    match ctx.storage.edit_product(key, changes).await? {
+        Some(product) => Ok(Response::new("Updated Product", show_product(&product)).into()),
+        None => Ok(no_such_product())
+    }
+}
+
+/// The product described by `addproduct`'s arguments, explaining the first one that doesn't make sense.
+fn parse_product(args: &[String]) -> Result<Product, String> {
+    let mut product = Product {
+        key: args[0].to_string(),
+        name: args[1].to_string(),
+        description: args[2].to_string(),
+        price: parse_count("price", &args[3])?,
+        quantity: parse_count("quantity", &args[4])?,
+        member_limit: 0,
+        unlimited: false,
+        digital: false
+    };
+    parse_changes(&args[5..])?.apply(&mut product);
+    Ok(product)
+}
+
+/// A price, quantity or limit, which can't be negative.
+fn parse_count(field: &str, value: &str) -> Result<i64, String> {
+    match value.parse::<i64>() {
+        Ok(number) if number >= 0 => Ok(number),
+        _ => Err(format!("{} should be a whole number of at least 0, not `{}`", field, value))
+    }
+}
+
+/// Reads `field=value` pairs, explaining the first one that doesn't make sense.
+fn parse_changes(args: &[String]) -> Result<ProductChanges, String> {
+    let mut changes = ProductChanges::default();
+    for arg in args {
+        let (field, value) = match arg.split_once('=') {
+            Some(pair) => pair,
+            None => return Err(format!("`{}` should look like field=value", arg))
+        };
+        match field {
+            "name" => changes.name = Some(value.to_string()),
+            "description" => changes.description = Some(value.to_string()),
+            "price" => changes.price = Some(parse_count(field, value)?),
+            "quantity" => changes.quantity = Some(parse_count(field, value)?),
+            "limit" => changes.member_limit = Some(parse_count(field, value)?),
+            "unlimited" => changes.unlimited = Some(match value {
+                "yes" | "true" => true,
+                "no" | "false" => false,
+                _ => return Err(format!("unlimited should be yes or no, not `{}`", value))
+            }),
+            _ => return Err(format!("Products don't have a `{}`; try name, description, price, quantity, limit or unlimited", field))
+        }
+    }
+    Ok(changes)
+}
+
+/// `[key] [amount]`
+pub async fn restock(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let (key, amount) = match (args.first(), args.get(1).and_then(|x| x.parse::<i64>().ok())) {
+        (Some(key), Some(amount)) if amount > 0 => (key, amount),
+        _ => return Ok(ctx.usage("restock [key] [amount]"))
+    };
+    if let Some(product) = ctx.storage.get_product(key).await?.filter(|product| product.digital) {
+        return Ok(Response::error("Cannot restock product", format!("{} is sold as codes, so add more with addcodes instead", product.name)).into());
+    }
+
+    match ctx.storage.restock(key, amount).await? {
+        Some(product) => Ok(Response::new("Restocked Product", show_product(&product)).into()),
+        None => Ok(no_such_product())
+    }
+}
+
+/// `[key] [code]...`
+pub async fn addcodes(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+
+    let (key, codes) = match args.split_first() {
+        Some((key, codes)) if !codes.is_empty() => (key, codes),
+        _ => return Ok(ctx.usage("addcodes [key] [code]..."))
+    };
+
+    match ctx.storage.add_codes(key, codes).await? {
+        Some(product) => Ok(Response::new("Added Codes", show_product(&product)).into()),
+        None => Ok(no_such_product())
+    }
+}
+
+fn no_such_product() -> Reply {
+    purchase_response(PurchaseOutcome::NoSuchProduct).into()
+}
+
+/// `[product_id]`
+pub async fn buy(ctx: &CommandContext<'_>, args: &[String]) -> Result<Reply, String> {
+    let key = match args.first() {
+        Some(key) => key,
+        None => return Ok(ctx.usage("buy [product_id]"))
+    };
+
+    let purchase = Purchase {
+        id: Uuid::new_v4().to_string(),
+        product_key: key.to_string(),
+        discord_id: ctx.caller.id.to_string(),
+        // Like the entry's credits, the store fills this in with what it charges
+        price: 0,
+        timestamp: storage::now(),
+        status: PurchaseStatus::Pending,
+        code: None
+    };
+    let id = purchase.id.clone();
+    let entry = ctx.ledger_entry(ctx.caller.id, 0, 0, &format!("Bought {}", key));
+    match ctx.storage.buy(purchase, entry).await? {
+        PurchaseOutcome::Purchased { product, profile, code: Some(code) } => deliver_code(ctx, &id, &product, &profile, &code).await,
+        outcome => Ok(purchase_response(outcome).into())
+    }
+}
+
+/// DMs the buyer their code, so it's never posted where others can see it. The order stays
+/// pending if the DM doesn't go through, for an admin to `fulfil` once they accept DMs.
+async fn deliver_code(ctx: &CommandContext<'_>, id: &str, product: &Product, profile: &Profile, code: &str) -> Result<Reply, String> {
+    let message = Response::new(format!("Your code for {}", product.name), format!("`{}`", code));
+    let delivery = match ctx.directory.direct_message(ctx.caller.id, message).await {
+        Ok(()) => {
+            ctx.storage.update_purchase_status(id, PurchaseStatus::Pending, PurchaseStatus::Fulfilled).await?;
+            "Your code is in your DMs"
+        },
+        Err(_) => "We couldn't DM you the code, so let an admin know once you allow DMs from this server"
+    };
+    Ok(Response::new("Purchase successful", format!("You just purchased a {}\n{}\nYou have {} :gem: left", product.name, delivery, profile.credits)).into())
+}
+
+fn purchase_response(outcome: PurchaseOutcome) -> Response {
+    match outcome {
+        PurchaseOutcome::Purchased { product, profile, .. } => {
+            Response::new("Purchase successful", format!("You just purchased a {}\nYou have {} :gem: left", product.name, profile.credits))
+        },
+        PurchaseOutcome::OutOfStock(product) => {
+            Response::error("Out of stock", format!("Sorry, we don't have any more of: {}", product.name))
+        },
+        PurchaseOutcome::LimitReached(product) => {
+            Response::error("Purchase limit reached", format!("Each member can only buy {} of {}, and you've already bought that many", product.member_limit, product.name))
+        },
+        PurchaseOutcome::CannotAfford { product, credits } => {
+            Response::error("You can't afford that!", format!("You only have {} :gem:, but \"{}\" costs {} :gem:", credits, product.name, product.price))
+        },
+        PurchaseOutcome::NoSuchProduct => {
+            Response::error("Cannot find product", "Could not find the product you are refering to")
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuyCheck {
+    Refused(Reply),
+    /// Ask the caller `prompt`, then `buy` the product with `key` if they agree
+    NeedsConfirmation { key: String, prompt: Response }
+}
+
+/// Checks the caller could buy `key` right now, without buying it.
+pub async fn check_buy(ctx: &CommandContext<'_>, key: &str) -> Result<BuyCheck, String> {
+    let product = match ctx.storage.get_product(key).await? {
+        Some(product) => product,
+        None => return Ok(BuyCheck::Refused(purchase_response(PurchaseOutcome::NoSuchProduct).into()))
+    };
+    let profile = ctx.storage.get_profile(&ctx.caller.id.to_string()).await?;
+    let bought = ctx.storage.get_purchases_by_member(&ctx.caller.id.to_string()).await?;
+    if let Some(refusal) = storage::refuse_purchase(&profile, &product, storage::count_bought(&bought, &product.key)) {
+        return Ok(BuyCheck::Refused(purchase_response(refusal).into()));
+    }
+
+    let prompt = Response::new("Confirm purchase", format!("Buy {} for {} :gem:?\nYou have {} :gem:", product.name, product.price, profile.credits));
+    Ok(BuyCheck::NeedsConfirmation { key: product.key, prompt })
+}
+
+pub fn cancelled_buy() -> Reply {
+    Response::new("Purchase cancelled", "No gems were spent").into()
+}
+
+/// Products whose key or name contains what's been typed so far, as `(label, key)`
+/// pairs to suggest while someone fills in a product key.
+pub async fn product_choices(storage: &dyn Storage, typed: &str) -> Result<Vec<(String, String)>, String> {
+    let typed = typed.to_lowercase();
+    let mut products: Vec<Product> = storage.get_store().await?.into_iter()
+        .filter(|product| product.key.to_lowercase().contains(&typed) || product.name.to_lowercase().contains(&typed))
+        .collect();
+    products.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(products.into_iter()
+        .take(MAX_CHOICES)
+        .map(|product| (label(&format!("{} ({} gems, {})", product.name, product.price, show_stock(&product))), product.key))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ERROR_COLOUR;
+    use crate::commands::fake::{FakeGuild, args, embed, ADMIN, MEMBER};
+    use crate::commands::points::givegems;
+
+    #[tokio::test]
+    async fn products_can_be_added_listed_and_deleted() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+
+        let response = embed(addproduct(&admin, &args("hoodie \"Club hoodie\" \"Warm and purple\" 50 2")).await.unwrap());
+        assert_eq!(response.description, "`hoodie`: **Club hoodie** (50 :gem:, 2 left)\nWarm and purple");
+        assert_eq!(store_pages(&admin).await.unwrap()[0].response.description, response.description);
+
+        embed(delproduct(&admin, &args("hoodie")).await.unwrap());
+        assert!(store_pages(&admin).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_admins_can_change_the_store() {
+        let guild = FakeGuild::new();
+        let member = guild.as_user(MEMBER);
+        assert_eq!(addproduct(&member, &args("hoodie Hoodie Warm 50 2")).await.unwrap(), Reply::Nothing);
+        assert_eq!(delproduct(&member, &args("hoodie")).await.unwrap(), Reply::Nothing);
+        assert_eq!(embed(addproduct(&guild.as_user(ADMIN), &args("hoodie Hoodie")).await.unwrap()).title, "Usage");
+        let response = embed(addproduct(&guild.as_user(ADMIN), &args("hoodie Hoodie Warm -50 2")).await.unwrap());
+        assert_eq!(response.title, "Cannot add product");
+        assert!(response.description.starts_with("price should be a whole number of at least 0, not `-50`"));
+        let response = embed(addproduct(&guild.as_user(ADMIN), &args("hoodie Hoodie Warm 50 lots")).await.unwrap());
+        assert!(response.description.starts_with("quantity should be a whole number of at least 0"));
+    }
+
+    #[tokio::test]
+    async fn buy_explains_each_refusal() {
+        let guild = FakeGuild::new();
+        let member = guild.as_user(MEMBER);
+        addproduct(&guild.as_user(ADMIN), &args("hoodie Hoodie Warm 50 1")).await.unwrap();
+
+        let response = embed(buy(&member, &args("sticker")).await.unwrap());
+        assert_eq!((response.title.as_str(), response.colour), ("Cannot find product", Some(ERROR_COLOUR)));
+        let response = embed(buy(&member, &args("hoodie")).await.unwrap());
+        assert_eq!(response.description, "You only have 0 :gem:, but \"Hoodie\" costs 50 :gem:");
+
+        givegems(&guild.as_user(ADMIN), &args(&format!("{} 120", MEMBER))).await.unwrap();
+        let response = embed(buy(&member, &args("hoodie")).await.unwrap());
+        assert_eq!(response.description, "You just purchased a Hoodie\nYou have 70 :gem: left");
+        let response = embed(buy(&member, &args("hoodie")).await.unwrap());
+        assert_eq!(response.title, "Out of stock");
+        assert_eq!(embed(buy(&member, &[]).await.unwrap()).title, "Usage");
+    }
+
+    #[tokio::test]
+    async fn store_pages_hold_a_few_products_each() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        for key in &["a", "b", "c", "d", "e", "f", "g"] {
+            addproduct(&admin, &args(&format!("{} Name Description 1 1", key))).await.unwrap();
+        }
+
+        let pages = store_pages(&admin).await.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].products.iter().map(|product| product.key.as_str()).collect::<Vec<&str>>(), vec!["f", "g"]);
+        assert!(pages[1].response.description.starts_with("`f`"));
+    }
+
+    #[tokio::test]
+    async fn buying_from_the_store_is_checked_before_confirming() {
+        let guild = FakeGuild::new();
+        let member = guild.as_user(MEMBER);
+        addproduct(&guild.as_user(ADMIN), &args("hoodie Hoodie Warm 50 1")).await.unwrap();
+
+        match check_buy(&member, "hoodie").await.unwrap() {
+            BuyCheck::Refused(reply) => assert_eq!(embed(reply).title, "You can't afford that!"),
+            other => panic!("Expected a refusal, got {:?}", other)
+        }
+
+        givegems(&guild.as_user(ADMIN), &args(&format!("{} 60", MEMBER))).await.unwrap();
+        match check_buy(&member, "hoodie").await.unwrap() {
+            BuyCheck::NeedsConfirmation { key, prompt } => {
+                assert_eq!(key, "hoodie");
+                assert_eq!(prompt.description, "Buy Hoodie for 50 :gem:?\nYou have 60 :gem:");
+            },
+            other => panic!("Expected a confirmation, got {:?}", other)
+        }
+        // Nothing is bought until it's confirmed
+        assert_eq!(guild.storage.get_product("hoodie").await.unwrap().unwrap().quantity, 1);
+    }
+
+    #[tokio::test]
+    async fn product_choices_match_key_or_name() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        addproduct(&admin, &args("hoodie \"Club Hoodie\" Warm 50 2")).await.unwrap();
+        addproduct(&admin, &args("sticker Sticker Shiny 5 10")).await.unwrap();
+
+        let choices = product_choices(&guild.storage, "HOOD").await.unwrap();
+        assert_eq!(choices, vec![("Club Hoodie (50 gems, 2 left)".to_string(), "hoodie".to_string())]);
+        assert_eq!(product_choices(&guild.storage, "").await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn labels_are_cut_to_fit_menus() {
+        assert_eq!(label("Club Hoodie"), "Club Hoodie");
+        let long = label(&"é".repeat(150));
+        assert_eq!((long.chars().count(), long.ends_with("é…")), (100, true));
+    }
+
+    #[tokio::test]
+    async fn products_can_be_edited_field_by_field() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        addproduct(&admin, &args("hoodie Hoodie Warm 50 2")).await.unwrap();
+
+        let response = embed(editproduct(&admin, &args("hoodie name=\"Club hoodie\" price=40")).await.unwrap());
+        assert_eq!((response.title.as_str(), response.description.as_str()), ("Updated Product", "`hoodie`: **Club hoodie** (40 :gem:, 2 left)\nWarm"));
+
+        let response = embed(editproduct(&admin, &args("hoodie colour=purple")).await.unwrap());
+        assert!(response.description.starts_with("Products don't have a `colour`"));
+        let response = embed(editproduct(&admin, &args("hoodie price=-1")).await.unwrap());
+        assert!(response.description.starts_with("price should be a whole number of at least 0"));
+        assert_eq!(embed(editproduct(&admin, &args("hoodie")).await.unwrap()).title, "Usage");
+        assert_eq!(embed(editproduct(&admin, &args("sticker price=1")).await.unwrap()).title, "Cannot find product");
+        assert_eq!(editproduct(&guild.as_user(MEMBER), &args("hoodie price=1")).await.unwrap(), Reply::Nothing);
+    }
+
+    #[tokio::test]
+    async fn restocking_adds_to_the_quantity() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        addproduct(&admin, &args("hoodie Hoodie Warm 50 2")).await.unwrap();
+
+        let response = embed(restock(&admin, &args("hoodie 3")).await.unwrap());
+        assert_eq!(response.description, "`hoodie`: **Hoodie** (50 :gem:, 5 left)\nWarm");
+        assert_eq!(embed(restock(&admin, &args("hoodie 0")).await.unwrap()).title, "Usage");
+        assert_eq!(embed(restock(&admin, &args("sticker 3")).await.unwrap()).title, "Cannot find product");
+        assert_eq!(restock(&guild.as_user(MEMBER), &args("hoodie 3")).await.unwrap(), Reply::Nothing);
+    }
+
+    #[tokio::test]
+    async fn buy_enforces_member_limits_and_unlimited_stock() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        let member = guild.as_user(MEMBER);
+        let response = embed(addproduct(&admin, &args("hoodie Hoodie Warm 10 5 limit=1")).await.unwrap());
+        assert_eq!(response.description, "`hoodie`: **Hoodie** (10 :gem:, 5 left, 1 per member)\nWarm");
+        let response = embed(addproduct(&admin, &args("perk Perk Shiny 10 0 unlimited=yes")).await.unwrap());
+        assert_eq!(response.description, "`perk`: **Perk** (10 :gem:, unlimited)\nShiny");
+        assert_eq!(embed(addproduct(&admin, &args("perk Perk Shiny 10 0 unlimited=maybe")).await.unwrap()).title, "Cannot add product");
+        givegems(&admin, &args(&format!("{} 100", MEMBER))).await.unwrap();
+
+        assert_eq!(embed(buy(&member, &args("hoodie")).await.unwrap()).title, "Purchase successful");
+        let response = embed(buy(&member, &args("hoodie")).await.unwrap());
+        assert_eq!((response.title.as_str(), response.colour), ("Purchase limit reached", Some(ERROR_COLOUR)));
+        match check_buy(&member, "hoodie").await.unwrap() {
+            BuyCheck::Refused(reply) => assert_eq!(embed(reply).title, "Purchase limit reached"),
+            other => panic!("Expected a refusal, got {:?}", other)
+        }
+
+        assert_eq!(embed(buy(&member, &args("perk")).await.unwrap()).title, "Purchase successful");
+        assert_eq!(embed(buy(&member, &args("perk")).await.unwrap()).title, "Purchase successful");
+    }
+
+    #[tokio::test]
+    async fn digital_codes_are_only_sent_by_dm() {
+        let guild = FakeGuild::new();
+        let admin = guild.as_user(ADMIN);
+        addproduct(&admin, &args("voucher \"Course voucher\" \"One free course\" 10 0")).await.unwrap();
+        let response = embed(addcodes(&admin, &args("voucher SECRET-1")).await.unwrap());
+        assert_eq!(response.description, "`voucher`: **Course voucher** (10 :gem:, 1 left)\nOne free course");
+        assert_eq!(embed(addcodes(&admin, &args("voucher")).await.unwrap()).title, "Usage");
+        assert_eq!(embed(restock(&admin, &args("voucher 3")).await.unwrap()).title, "Cannot restock product");
+        assert_eq!(embed(editproduct(&admin, &args("voucher quantity=5")).await.unwrap()).title, "Cannot edit product");
+        assert_eq!(embed(editproduct(&admin, &args("voucher unlimited=yes")).await.unwrap()).title, "Cannot edit product");
+        assert_eq!(embed(editproduct(&admin, &args("voucher price=20")).await.unwrap()).title, "Updated Product");
+        assert_eq!(embed(addproduct(&admin, &args("voucher Voucher Free 10 5")).await.unwrap()).title, "Cannot add product");
+        editproduct(&admin, &args("voucher price=10")).await.unwrap();
+        assert!(!store_pages(&admin).await.unwrap()[0].response.description.contains("SECRET"));
+        givegems(&admin, &args(&format!("{} 10", MEMBER))).await.unwrap();
+
+        let response = embed(buy(&guild.as_user(MEMBER), &args("voucher")).await.unwrap());
+        assert_eq!(response.description, "You just purchased a Course voucher\nYour code is in your DMs\nYou have 0 :gem: left");
+        let direct_messages = guild.directory.direct_messages.lock().unwrap().clone();
+        assert_eq!((direct_messages[0].0, direct_messages[0].1.description.as_str()), (MEMBER, "`SECRET-1`"));
+        let purchases = guild.storage.get_purchases_by_member(&MEMBER.to_string()).await.unwrap();
+        assert_eq!(purchases[0].status, PurchaseStatus::Fulfilled);
+    }
+}
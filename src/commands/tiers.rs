@@ -0,0 +1,182 @@
+use std::collections::BTreeSet;
+
+use crate::config::RoleTier;
+use super::{CommandContext, Reply, Response};
+
+/// The highest tier someone with `points` has reached, if any.
+pub fn tier_for(tiers: &[RoleTier], points: i64) -> Option<&RoleTier> {
+    tiers.iter()
+        .filter(|tier| tier.points <= points)
+        .max_by_key(|tier| tier.points)
+}
+
+/// Tier roles given to and taken from a member.
+#[derive(Debug, Default, PartialEq)]
+pub struct RoleChanges {
+    pub added: Vec<u64>,
+    pub removed: Vec<u64>
+}
+
+/// Gives a member the role for the tier their points reach and takes away any other tier
+/// roles, so they only ever hold one. When `announce` is set and they've gone up a tier,
+/// says so in the announcement channel.
+pub async fn sync_roles(ctx: &CommandContext<'_>, user_id: u64, points: i64, announce: bool) -> Result<RoleChanges, String> {
+    let tiers = &ctx.config.role_tiers;
+    if tiers.is_empty() {
+        return Ok(RoleChanges::default());
+    }
+
+    let target = tier_for(tiers, points);
+    let held = ctx.directory.member_roles(user_id).await?;
+    let mut changes = RoleChanges::default();
+    for tier in tiers {
+        let holds = held.contains(&tier.role);
+        if target == Some(tier) && !holds {
+            ctx.directory.add_role(user_id, tier.role).await?;
+            changes.added.push(tier.role);
+        } else if target != Some(tier) && holds {
+            ctx.directory.remove_role(user_id, tier.role).await?;
+            changes.removed.push(tier.role);
+        }
+    }
+
+    // Only going up is worth announcing, not losing a tier after a refund or correction
+    let promoted = match target {
+        Some(target) if changes.added.contains(&target.role) => tiers.iter()
+            .filter(|tier| changes.removed.contains(&tier.role))
+            .all(|tier| tier.points < target.points),
+        _ => false
+    };
+    if let (true, true, Some(channel), Some(target)) = (announce, promoted, ctx.config.announce_channel, target) {
+        let message = Response::new("New tier reached!", format!("<@{}> reached <@&{}> with {} :star:!", user_id, target.role, points));
+        ctx.directory.post(channel, message).await?;
+    }
+    Ok(changes)
+}
+
+/// Brings a member's tier role up to date after their points change. Points have already
+/// been given by now, so a role that can't be updated is only logged; `syncroles` fixes it.
+pub async fn points_changed(ctx: &CommandContext<'_>, user_id: u64, points: i64) {
+    if let Err(err) = sync_roles(ctx, user_id, points, true).await {
+        println!("Error updating tier roles for {}: {:?}", user_id, err);
+    }
+}
+
+/// Gives every member the tier role their points reach, for after tiers are changed or
+/// roles have been handed out by hand.
+pub async fn syncroles(ctx: &CommandContext<'_>, _args: &[String]) -> Result<Reply, String> {
+    if !ctx.caller.is_admin {
+        return Ok(Reply::Nothing);
+    }
+    if ctx.config.role_tiers.is_empty() {
+        return Ok(Response::error("No tiers", "There are no role tiers set up").into());
+    }
+
+    // Members holding a tier role without a profile still need it taken away
+    let profiles = ctx.storage.get_profiles().await?;
+    let tier_roles: Vec<u64> = ctx.config.role_tiers.iter().map(|tier| tier.role).collect();
+    let holders = ctx.directory.members_with_roles(&tier_roles).await?;
+    let mut members: BTreeSet<(u64, i64)> = BTreeSet::new();
+    for (discord_id, profile) in &profiles {
+        if let Ok(user_id) = discord_id.parse::<u64>() {
+            members.insert((user_id, profile.points));
+        }
+    }
+    for (user_id, _) in holders {
+        if !profiles.iter().any(|(discord_id, _)| *discord_id == user_id.to_string()) {
+            members.insert((user_id, 0));
+        }
+    }
+
+    let (mut added, mut removed, mut failed) = (0, 0, 0);
+    for (user_id, points) in &members {
+        match sync_roles(ctx, *user_id, *points, false).await {
+            Ok(changes) => {
+                added += changes.added.len();
+                removed += changes.removed.len();
+            },
+            Err(err) => {
+                println!("Error updating tier roles for {}: {:?}", user_id, err);
+                failed += 1;
+            }
+        }
+    }
+
+    let mut description = format!("Checked {} members: {} roles given, {} taken away", members.len(), added, removed);
+    if failed > 0 {
+        description.push_str(&format!("\n{} members couldn't be updated, they may have left the server", failed));
+    }
+    Ok(Response::new("Roles synced", description).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::fake::{FakeGuild, args, embed, ADMIN, MEMBER, OTHER_MEMBER};
+    use crate::commands::points::givepoints;
+    use crate::commands::{Directory, ERROR_COLOUR};
+    use crate::storage::Storage;
+
+    const CONTRIBUTOR: u64 = 300000000000000001;
+    const VETERAN: u64 = 300000000000000002;
+    const ANNOUNCEMENTS: u64 = 400000000000000001;
+
+    fn tiered_guild() -> FakeGuild {
+        let mut guild = FakeGuild::new();
+        guild.config.role_tiers = vec![RoleTier { points: 200, role: VETERAN }, RoleTier { points: 50, role: CONTRIBUTOR }];
+        guild.config.announce_channel = Some(ANNOUNCEMENTS);
+        guild
+    }
+
+    #[test]
+    fn members_reach_the_highest_tier_their_points_cover() {
+        let tiers = tiered_guild().config.role_tiers;
+        assert_eq!(tier_for(&tiers, 49), None);
+        assert_eq!(tier_for(&tiers, 50).map(|tier| tier.role), Some(CONTRIBUTOR));
+        assert_eq!(tier_for(&tiers, 250).map(|tier| tier.role), Some(VETERAN));
+    }
+
+    #[tokio::test]
+    async fn points_move_members_between_tiers() {
+        let guild = tiered_guild();
+        let admin = guild.as_user(ADMIN);
+
+        givepoints(&admin, &args(&format!("{} 60 \"Helping out\"", MEMBER))).await.unwrap();
+        assert!(guild.directory.roles(MEMBER).contains(&CONTRIBUTOR));
+        givepoints(&admin, &args(&format!("{} 150 \"Running an event\"", MEMBER))).await.unwrap();
+        let roles = guild.directory.roles(MEMBER);
+        assert!(roles.contains(&VETERAN) && !roles.contains(&CONTRIBUTOR));
+
+        let posts = guild.directory.posts.lock().unwrap().clone();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[1].0, ANNOUNCEMENTS);
+        assert_eq!(posts[1].1.description, format!("<@{}> reached <@&{}> with 210 :star:!", MEMBER, VETERAN));
+
+        // Dropping a tier takes the role away without announcing it
+        givepoints(&admin, &args(&format!("{} -20 \"Correction\"", MEMBER))).await.unwrap();
+        let roles = guild.directory.roles(MEMBER);
+        assert!(roles.contains(&CONTRIBUTOR) && !roles.contains(&VETERAN));
+        assert_eq!(guild.directory.posts.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn syncroles_fixes_roles_given_by_hand() {
+        let guild = tiered_guild();
+        assert_eq!(syncroles(&guild.as_user(MEMBER), &[]).await.unwrap(), Reply::Nothing);
+
+        guild.storage.record_entry(guild.as_user(ADMIN).ledger_entry(MEMBER, 300, 0, "Imported")).await.unwrap();
+        guild.directory.add_role(OTHER_MEMBER, VETERAN).await.unwrap();
+
+        let response = embed(syncroles(&guild.as_user(ADMIN), &[]).await.unwrap());
+        assert_eq!(response.description, "Checked 2 members: 1 roles given, 1 taken away");
+        assert!(guild.directory.roles(MEMBER).contains(&VETERAN));
+        assert!(!guild.directory.roles(OTHER_MEMBER).contains(&VETERAN));
+        assert!(guild.directory.posts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn syncroles_needs_tiers() {
+        let guild = FakeGuild::new();
+        assert_eq!(embed(syncroles(&guild.as_user(ADMIN), &[]).await.unwrap()).colour, Some(ERROR_COLOUR));
+    }
+}
@@ -0,0 +1,301 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use rusoto_core::Region;
+use serde::Deserialize;
+use serenity::client::Context;
+use serenity::prelude::TypeMapKey;
+
+use crate::commands::activities;
+
+/// Where the config file is read from unless `CONFIG_PATH` says otherwise.
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Everything about the bot that differs between deployments. Read from a TOML
+/// file, then any of the environment variables named on each field override it.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// `COMMAND_PREFIX`
+    pub prefix: String,
+    /// Members with any of these roles can run admin commands. `ADMIN_ROLES`, comma separated
+    pub admin_roles: Vec<u64>,
+    /// Hex colour like `#6e10aa`. `EMBED_COLOUR`
+    pub embed_colour: String,
+    /// TOML catalogue of activities, like `activities.toml`. `ACTIVITIES_PATH`
+    pub activities_path: String,
+    /// Channel where activity claims are posted for admins to approve or reject. `REVIEW_CHANNEL`
+    pub review_channel: Option<u64>,
+    /// Roles for reaching points thresholds. Members hold the role for the highest one they've
+    /// reached. `ROLE_TIERS`, like `50:<role id>,200:<role id>`
+    pub role_tiers: Vec<RoleTier>,
+    /// Channel where members reaching a new tier are announced. `ANNOUNCE_CHANNEL`
+    pub announce_channel: Option<u64>,
+    pub storage: StorageConfig,
+    pub aws: AwsConfig,
+    pub tables: TablesConfig
+}
+
+/// A role members get once they have at least `points`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RoleTier {
+    pub points: i64,
+    pub role: u64
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// `dynamodb`, `sqlite` or `memory`. `STORAGE_BACKEND`
+    pub backend: String,
+    /// `SQLITE_PATH`
+    pub sqlite_path: String
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AwsConfig {
+    /// `AWS_REGION`
+    pub region: String,
+    /// Talk to this endpoint instead of AWS, e.g. DynamoDB Local. `DYNAMODB_ENDPOINT`
+    pub endpoint: Option<String>
+}
+
+/// DynamoDB table names. `TABLE_PROFILES`, `TABLE_STORE`, `TABLE_PURCHASES`, `TABLE_LEDGER`, `TABLE_CODES`,
+/// `TABLE_CLAIMS`, `TABLE_EVENTS`, `TABLE_EVENT_CODES` and `TABLE_ATTENDANCE`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TablesConfig {
+    pub profiles: String,
+    pub store: String,
+    pub purchases: String,
+    pub ledger: String,
+    /// Unsold codes for digital products
+    pub codes: String,
+    /// Activity claims waiting on or reviewed by admins
+    pub claims: String,
+    /// Events members check in to with a code
+    pub events: String,
+    /// Which event each check-in code belongs to, so no two open events share one
+    pub event_codes: String,
+    /// Who was at each event
+    pub attendance: String
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            prefix: "~".to_string(),
+            admin_roles: vec![449076533223751691, 778454540814909472],
+            embed_colour: "#6e10aa".to_string(),
+            activities_path: "activities.toml".to_string(),
+            review_channel: None,
+            role_tiers: Vec::new(),
+            announce_channel: None,
+            storage: Default::default(),
+            aws: Default::default(),
+            tables: Default::default()
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig { backend: "dynamodb".to_string(), sqlite_path: "leadership.db".to_string() }
+    }
+}
+
+impl Default for AwsConfig {
+    fn default() -> Self {
+        AwsConfig { region: "us-east-1".to_string(), endpoint: None }
+    }
+}
+
+impl Default for TablesConfig {
+    fn default() -> Self {
+        TablesConfig {
+            profiles: "TPCMemberPoints".to_string(),
+            store: "TPCStore".to_string(),
+            purchases: "TPCPurchases".to_string(),
+            ledger: "TPCLedger".to_string(),
+            codes: "TPCCodes".to_string(),
+            claims: "TPCClaims".to_string(),
+            events: "TPCEvents".to_string(),
+            event_codes: "TPCEventCodes".to_string(),
+            attendance: "TPCAttendance".to_string()
+        }
+    }
+}
+
+impl Config {
+    /// Reads the file at `CONFIG_PATH` (or `config.toml`), applies environment
+    /// overrides and validates the result. A missing `config.toml` just means defaults.
+    pub fn load() -> Result<Config, String> {
+        let config = match env::var("CONFIG_PATH") {
+            Ok(path) => Config::from_file(&path)?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => Config::from_file(DEFAULT_CONFIG_PATH)?,
+            Err(_) => Config::default()
+        };
+        let config = config.with_env_overrides(|name| env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &str) -> Result<Config, String> {
+        let contents = fs::read_to_string(path).map_err(|err| format!("Could not read config file {}: {}", path, err))?;
+        Config::from_toml(&contents).map_err(|err| format!("Invalid config file {}: {}", path, err))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Config, String> {
+        toml::from_str(contents).map_err(|err| err.to_string())
+    }
+
+    /// Replaces settings with any overrides `lookup` finds for their environment variable.
+    pub fn with_env_overrides<F: Fn(&str) -> Option<String>>(mut self, lookup: F) -> Result<Config, String> {
+        if let Some(prefix) = lookup("COMMAND_PREFIX") {
+            self.prefix = prefix;
+        }
+        if let Some(roles) = lookup("ADMIN_ROLES") {
+            self.admin_roles = roles.split(',')
+                .map(str::trim)
+                .filter(|role| !role.is_empty())
+                .map(|role| role.parse::<u64>().map_err(|_| format!("ADMIN_ROLES: {} is not a role ID", role)))
+                .collect::<Result<Vec<u64>, String>>()?;
+        }
+        if let Some(colour) = lookup("EMBED_COLOUR") {
+            self.embed_colour = colour;
+        }
+        if let Some(path) = lookup("ACTIVITIES_PATH") {
+            self.activities_path = path;
+        }
+        if let Some(channel) = lookup("REVIEW_CHANNEL") {
+            self.review_channel = Some(channel.trim().parse::<u64>().map_err(|_| format!("REVIEW_CHANNEL: {} is not a channel ID", channel))?);
+        }
+        if let Some(tiers) = lookup("ROLE_TIERS") {
+            self.role_tiers = tiers.split(',')
+                .map(str::trim)
+                .filter(|tier| !tier.is_empty())
+                .map(|tier| match tier.split_once(':') {
+                    Some((points, role)) => match (points.trim().parse::<i64>(), role.trim().parse::<u64>()) {
+                        (Ok(points), Ok(role)) => Ok(RoleTier { points, role }),
+                        _ => Err(format!("ROLE_TIERS: {} is not points:role ID", tier))
+                    },
+                    None => Err(format!("ROLE_TIERS: {} is not points:role ID", tier))
+                })
+                .collect::<Result<Vec<RoleTier>, String>>()?;
+        }
+        if let Some(channel) = lookup("ANNOUNCE_CHANNEL") {
+            self.announce_channel = Some(channel.trim().parse::<u64>().map_err(|_| format!("ANNOUNCE_CHANNEL: {} is not a channel ID", channel))?);
+        }
+        if let Some(backend) = lookup("STORAGE_BACKEND") {
+            self.storage.backend = backend;
+        }
+        if let Some(path) = lookup("SQLITE_PATH") {
+            self.storage.sqlite_path = path;
+        }
+        if let Some(region) = lookup("AWS_REGION") {
+            self.aws.region = region;
+        }
+        if let Some(endpoint) = lookup("DYNAMODB_ENDPOINT") {
+            self.aws.endpoint = Some(endpoint);
+        }
+        if let Some(table) = lookup("TABLE_PROFILES") {
+            self.tables.profiles = table;
+        }
+        if let Some(table) = lookup("TABLE_STORE") {
+            self.tables.store = table;
+        }
+        if let Some(table) = lookup("TABLE_PURCHASES") {
+            self.tables.purchases = table;
+        }
+        if let Some(table) = lookup("TABLE_LEDGER") {
+            self.tables.ledger = table;
+        }
+        if let Some(table) = lookup("TABLE_CODES") {
+            self.tables.codes = table;
+        }
+        if let Some(table) = lookup("TABLE_CLAIMS") {
+            self.tables.claims = table;
+        }
+        if let Some(table) = lookup("TABLE_EVENTS") {
+            self.tables.events = table;
+        }
+        if let Some(table) = lookup("TABLE_EVENT_CODES") {
+            self.tables.event_codes = table;
+        }
+        if let Some(table) = lookup("TABLE_ATTENDANCE") {
+            self.tables.attendance = table;
+        }
+        Ok(self)
+    }
+
+    /// Checks every setting, so mistakes show up at startup rather than mid-command.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.prefix.is_empty() || self.prefix.contains(char::is_whitespace) {
+            return Err(format!("prefix must be non-empty with no spaces, got {:?}", self.prefix));
+        }
+        if self.admin_roles.is_empty() {
+            return Err("admin_roles must list at least one role ID".to_string());
+        }
+        parse_colour(&self.embed_colour)?;
+        activities::load_activities(&self.activities_path).map_err(|err| format!("activities_path: {}", err))?;
+        for (index, tier) in self.role_tiers.iter().enumerate() {
+            if self.role_tiers[..index].iter().any(|other| other.points == tier.points || other.role == tier.role) {
+                return Err(format!("role_tiers: each tier needs its own points and role, but {} points or role {} is used twice", tier.points, tier.role));
+            }
+        }
+        match self.storage.backend.as_str() {
+            "dynamodb" => {
+                self.region()?;
+                let tables = [&self.tables.profiles, &self.tables.store, &self.tables.purchases, &self.tables.ledger, &self.tables.codes,
+                              &self.tables.claims, &self.tables.events, &self.tables.event_codes, &self.tables.attendance];
+                if tables.iter().any(|table| table.is_empty()) {
+                    return Err("tables: table names can't be empty".to_string());
+                }
+            },
+            "sqlite" => {
+                if self.storage.sqlite_path.is_empty() {
+                    return Err("storage.sqlite_path can't be empty".to_string());
+                }
+            },
+            "memory" => {},
+            other => return Err(format!("storage.backend must be dynamodb, sqlite or memory, got {:?}", other))
+        }
+        Ok(())
+    }
+
+    pub fn colour(&self) -> u64 {
+        parse_colour(&self.embed_colour).unwrap_or(0)
+    }
+
+    /// The AWS region to use, pointing at `aws.endpoint` if one is set.
+    pub fn region(&self) -> Result<Region, String> {
+        match &self.aws.endpoint {
+            Some(endpoint) => Ok(Region::Custom { name: self.aws.region.clone(), endpoint: endpoint.clone() }),
+            None => Region::from_str(&self.aws.region).map_err(|_| format!("aws.region: {} is not an AWS region", self.aws.region))
+        }
+    }
+}
+
+fn parse_colour(colour: &str) -> Result<u64, String> {
+    let hex = colour.trim_start_matches('#');
+    match u64::from_str_radix(hex, 16) {
+        Ok(value) if hex.len() == 6 => Ok(value),
+        _ => Err(format!("embed_colour must be a hex colour like #6e10aa, got {:?}", colour))
+    }
+}
+
+pub struct ConfigKey;
+
+impl TypeMapKey for ConfigKey {
+    type Value = Arc<Config>;
+}
+
+pub async fn get(ctx: &Context) -> Arc<Config> {
+    let data = ctx.data.read().await;
+    data.get::<ConfigKey>().expect("Config has not been loaded").clone()
+}
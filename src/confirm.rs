@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use serenity::client::Context;
+use serenity::model::channel::{Message, ReactionType};
+use serenity::Error;
+
+use leadershipdiscordbot_rs::commands::Response;
+use leadershipdiscordbot_rs::config;
+
+use crate::reply;
+
+const CONFIRM: char = '✅';
+const CANCEL: char = '❌';
+
+/// How long the author has to confirm before we give up.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Asks the author of `msg` to confirm an action by reacting to an embed.
+/// Returns whether they confirmed; cancelling or not answering in time counts as no.
+pub async fn confirm(ctx: &Context, msg: &Message, prompt: &Response) -> Result<bool, Error> {
+    let colour = config::get(ctx).await.colour();
+    let description = format!("{}\n\nReact with {} to confirm or {} to cancel", prompt.description, CONFIRM, CANCEL);
+    let prompt = msg.channel_id.send_message(&ctx, |m| {
+        m.content("");
+        m.embed(|e| reply::embed(e, &Response { description, ..prompt.clone() }, colour));
+        m
+    }).await?;
+
+    prompt.react(&ctx, CONFIRM).await?;
+    prompt.react(&ctx, CANCEL).await?;
+
+    let answer = prompt.await_reaction(ctx)
+        .author_id(msg.author.id)
+        .filter(|reaction| reaction.emoji == ReactionType::from(CONFIRM) || reaction.emoji == ReactionType::from(CANCEL))
+        .timeout(CONFIRM_TIMEOUT)
+        .await;
+
+    Ok(match answer {
+        Some(action) => action.as_inner_ref().emoji == ReactionType::from(CONFIRM),
+        None => false
+    })
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use serenity::async_trait;
+use serenity::client::Context;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::channel::Message;
+use serenity::model::guild::Member;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::model::user::User;
+use serenity::Error;
+
+use leadershipdiscordbot_rs::{config, storage};
+use leadershipdiscordbot_rs::commands::{self, Caller, CommandContext, Directory, Response};
+use leadershipdiscordbot_rs::config::Config;
+use leadershipdiscordbot_rs::storage::Storage;
+
+use crate::reply;
+
+const MEMBERS_PAGE_SIZE: u64 = 1000;
+
+/// Looks members up through Discord, starting with anyone mentioned in the command.
+pub struct SerenityDirectory {
+    ctx: Context,
+    guild_id: Option<GuildId>,
+    mentions: Vec<User>,
+    /// For embeds sent in DMs
+    colour: u64
+}
+
+#[async_trait]
+impl Directory for SerenityDirectory {
+    async fn user_name(&self, user_id: u64) -> Option<String> {
+        if let Some(user) = self.mentions.iter().find(|user| user.id.0 == user_id) {
+            return Some(user.name.clone());
+        }
+        UserId(user_id).to_user(&self.ctx).await.ok().map(|user| user.name)
+    }
+
+    async fn members_with_roles(&self, role_ids: &[u64]) -> Result<Vec<(u64, String)>, String> {
+        let guild_id = match self.guild_id {
+            Some(guild_id) => guild_id,
+            None => return Ok(Vec::new())
+        };
+        let members = guild_members(&self.ctx, guild_id).await.map_err(|err| err.to_string())?;
+        Ok(members.into_iter()
+            .filter(|member| role_ids.iter().any(|role_id| member.roles.contains(&RoleId(*role_id))))
+            .map(|member| (member.user.id.0, member.user.name.clone()))
+            .collect())
+    }
+
+    async fn direct_message(&self, user_id: u64, message: Response) -> Result<(), String> {
+        let channel = UserId(user_id).create_dm_channel(&self.ctx).await.map_err(|err| err.to_string())?;
+        channel.send_message(&self.ctx, |m| m.embed(|e| reply::embed(e, &message, self.colour)))
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    async fn post(&self, channel_id: u64, message: Response) -> Result<(), String> {
+        ChannelId(channel_id).send_message(&self.ctx, |m| m.embed(|e| reply::embed(e, &message, self.colour)))
+            .await
+            .map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    async fn member_roles(&self, user_id: u64) -> Result<Vec<u64>, String> {
+        let guild_id = self.guild_id.ok_or("Roles only exist in a guild")?;
+        let member = guild_id.member(&self.ctx, user_id).await.map_err(|err| err.to_string())?;
+        Ok(member.roles.iter().map(|role| role.0).collect())
+    }
+
+    async fn add_role(&self, user_id: u64, role_id: u64) -> Result<(), String> {
+        let guild_id = self.guild_id.ok_or("Roles only exist in a guild")?;
+        self.ctx.http.add_member_role(guild_id.0, user_id, role_id, None).await.map_err(|err| err.to_string())
+    }
+
+    async fn remove_role(&self, user_id: u64, role_id: u64) -> Result<(), String> {
+        let guild_id = self.guild_id.ok_or("Roles only exist in a guild")?;
+        self.ctx.http.remove_member_role(guild_id.0, user_id, role_id, None).await.map_err(|err| err.to_string())
+    }
+}
+
+/// Fetches every member of a guild, a page at a time.
+async fn guild_members(ctx: &Context, guild_id: GuildId) -> Result<Vec<Member>, Error> {
+    let mut members: Vec<Member> = Vec::new();
+    loop {
+        let page = guild_id.members(&ctx.http, Some(MEMBERS_PAGE_SIZE), members.last().map(|member| member.user.id)).await?;
+        let done = (page.len() as u64) < MEMBERS_PAGE_SIZE;
+        members.extend(page);
+        if done {
+            return Ok(members);
+        }
+    }
+}
+
+fn is_admin(config: &Config, roles: Option<&Vec<RoleId>>) -> bool {
+    match roles {
+        Some(roles) => config.admin_roles.iter().any(|role_id| roles.contains(&RoleId(*role_id))),
+        None => false
+    }
+}
+
+/// Everything a command needs from a message or slash command, owned so a
+/// `CommandContext` can borrow it.
+pub struct Invocation {
+    storage: Arc<dyn Storage>,
+    config: Arc<Config>,
+    directory: SerenityDirectory,
+    caller: Caller,
+    pub args: Vec<String>
+}
+
+impl Invocation {
+    pub async fn from_message(ctx: &Context, msg: &Message) -> Invocation {
+        let config = config::get(ctx).await;
+        Invocation {
+            storage: storage::get(ctx).await,
+            directory: SerenityDirectory { ctx: ctx.clone(), guild_id: msg.guild_id, mentions: msg.mentions.clone(), colour: config.colour() },
+            caller: Caller {
+                id: msg.author.id.0,
+                name: msg.author.name.clone(),
+                is_admin: is_admin(&config, msg.member.as_ref().map(|member| &member.roles)),
+                message_id: msg.id.to_string()
+            },
+            args: commands::split_args(&msg.content),
+            config
+        }
+    }
+
+    /// `args` are the command's options, already put in the order the prefix command takes them.
+    pub async fn from_command(ctx: &Context, command: &ApplicationCommandInteraction, args: Vec<String>) -> Invocation {
+        let config = config::get(ctx).await;
+        let mentions = command.data.resolved.users.values().cloned().collect();
+        Invocation {
+            storage: storage::get(ctx).await,
+            directory: SerenityDirectory { ctx: ctx.clone(), guild_id: command.guild_id, mentions, colour: config.colour() },
+            caller: Caller {
+                id: command.user.id.0,
+                name: command.user.name.clone(),
+                is_admin: is_admin(&config, command.member.as_ref().map(|member| &member.roles)),
+                message_id: command.id.to_string()
+            },
+            args,
+            config
+        }
+    }
+
+    /// For buttons that run a command, like approving a claim; `args` are what the button stands for.
+    pub async fn from_component(ctx: &Context, component: &MessageComponentInteraction, args: Vec<String>) -> Invocation {
+        let config = config::get(ctx).await;
+        Invocation {
+            storage: storage::get(ctx).await,
+            directory: SerenityDirectory { ctx: ctx.clone(), guild_id: component.guild_id, mentions: Vec::new(), colour: config.colour() },
+            caller: Caller {
+                id: component.user.id.0,
+                name: component.user.name.clone(),
+                is_admin: is_admin(&config, component.member.as_ref().map(|member| &member.roles)),
+                message_id: component.id.to_string()
+            },
+            args,
+            config
+        }
+    }
+
+    pub fn context(&self) -> CommandContext<'_> {
+        CommandContext {
+            storage: &*self.storage,
+            directory: &self.directory,
+            config: &self.config,
+            caller: self.caller.clone()
+        }
+    }
+}
@@ -1,50 +1,114 @@
 
 use serenity::async_trait;
 use serenity::client::{Client, Context, EventHandler};
+use serenity::model::application::interaction::Interaction;
 use serenity::model::channel::Message;
-use serenity::Error;
+use serenity::model::gateway::{GatewayIntents, Ready};
 use serenity::framework::standard::{
     StandardFramework,
     CommandResult,
     macros::{
         command,
-        group
+        group,
+        hook
     }
 };
 
 use std::env;
-use std::collections::HashMap;
-use serenity::model::id::{RoleId, UserId, ChannelId};
-use serenity::static_assertions::_core::str::FromStr;
-use rusoto_core::Region;
-use rusoto_dynamodb::{DynamoDb, DynamoDbClient, PutItemInput, GetItemInput, AttributeValue, ScanInput, DeleteItemInput};
-use shell_words::split;
-use uuid::Uuid;
-use std::fs::File;
-use std::io::prelude::*;
+use std::process;
+use std::sync::Arc;
+
+mod confirm;
+mod invocation;
+mod pages;
+mod reply;
+mod review;
+mod slash;
+mod store_view;
+
+use leadershipdiscordbot_rs::{config, storage};
+use leadershipdiscordbot_rs::commands::{activities, claims, events, orders, points, store, tiers, Response};
+use leadershipdiscordbot_rs::commands::claims::Submission;
+use leadershipdiscordbot_rs::commands::points::Transfer;
+use leadershipdiscordbot_rs::config::{Config, ConfigKey};
+use leadershipdiscordbot_rs::storage::StorageKey;
+
+use invocation::Invocation;
+use reply::send_reply;
+use store_view::StoreState;
 
 
 #[group]
-#[commands(getpoints, givepoints, givegems, store, addproduct, buy, delproduct, activities)]
+#[commands(getpoints, history, leaderboard, givepoints, givegems, transfer, store, addproduct, editproduct, restock, addcodes, buy, delproduct, mypurchases, orders, fulfil, cancel, refund, activities, claim, approve, reject, addevent, checkin, addattendance, attendance, audit, syncroles)]
 struct General;
 
 struct Handler;
 
 #[async_trait]
-impl EventHandler for Handler {}
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        for guild in &ready.guilds {
+            if let Err(err) = guild.id.set_application_commands(&ctx.http, slash::register).await {
+                println!("Could not register slash commands in {}: {:?}", guild.id, err);
+            }
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let result = match &interaction {
+            Interaction::ApplicationCommand(command) => slash::run(&ctx, command).await,
+            Interaction::Autocomplete(autocomplete) => slash::autocomplete(&ctx, autocomplete).await,
+            Interaction::MessageComponent(component) => review::handle(&ctx, component).await,
+            _ => Ok(())
+        };
+        if let Err(err) = result {
+            println!("Error handling interaction: {:?}", err);
+        }
+    }
+}
+
+#[hook]
+async fn after(_ctx: &Context, _msg: &Message, command_name: &str, result: CommandResult) {
+    if let Err(err) = result {
+        println!("Error in {}: {:?}", command_name, err);
+    }
+}
 
 
 #[tokio::main]
 async fn main() {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            println!("Invalid configuration: {}", err);
+            process::exit(1);
+        }
+    };
+
     let framework = StandardFramework::new()
-        .configure(|c| c.prefix("~")) // set the bot's prefix to "~"
+        .configure(|c| c.prefix(&config.prefix))
+        .after(after)
         .group(&GENERAL_GROUP);
 
+    let storage = storage::from_config(&config).await.expect("Error setting up storage");
+    match storage::open_ledgers(storage.as_ref()).await {
+        Ok(0) => {},
+        Ok(opened) => println!("Added opening balances to the ledger for {} members", opened),
+        Err(err) => println!("Could not add opening balances to the ledger: {}", err)
+    }
+
     // Login with a bot token from the environment
     let token = env::var("DISCORD_TOKEN").expect("token");
-    let mut client = Client::builder(token)
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MEMBERS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::MESSAGE_CONTENT;
+    let mut client = Client::builder(token, intents)
         .event_handler(Handler)
         .framework(framework)
+        .type_map_insert::<StorageKey>(storage)
+        .type_map_insert::<ConfigKey>(Arc::new(config))
         .await
         .expect("Error creating client");
 
@@ -56,593 +120,266 @@ async fn main() {
 
 #[command]
 async fn getpoints(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut user_id = msg.author.id.to_string();
-
     msg.channel_id.broadcast_typing(&ctx).await?;
-
-    //get args
-    let mut content = msg.content.to_string();
-    content.remove(0);
-    let sections: Vec<&str> = content.split_ascii_whitespace().collect();
-
-    if msg.mentions.len() > 0 {
-        user_id = msg.mentions[0].id.to_string();
-    }
-    else if sections.len() > 1 {
-        user_id = sections[1].to_string();
-    }
-
-    let user = UserId::from_str(&user_id).unwrap_or(UserId(333)).to_user(ctx).await;
-    let username: String;
-    match user {
-        Ok(u) => {username = u.name}
-        _ => {
-            msg.channel_id.send_message(&ctx, |m| {
-                m.content("");
-                m.embed(|e| {
-                    e.title("Point Count");
-                    e.description("Could not find user");
-                    e.color(0x6e10aau64);
-                    e
-                });
-                m
-            }).await?;
-            return Ok(());
-        }
-    }
-
-    match get_profile(&user_id).await {
-        Ok(profile) => {
-            msg.channel_id.send_message(&ctx, |m| {
-                m.content("");
-                m.embed(|e| {
-                    e.title(username + "'s points");
-                    e.description(show_points(profile));
-
-                    e
-                });
-                m
-            }).await?;
-        }
-        ,
-        Err(err) => {
-            println!("Error: {:?}", err);
-        }
-    }
-
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = points::getpoints(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
     Ok(())
 }
 
-struct Profile {
-  points: i64,
-  credits: i64
+#[command]
+async fn history(ctx: &Context, msg: &Message) -> CommandResult {
+    msg.channel_id.broadcast_typing(&ctx).await?;
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = points::history(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-async fn get_profile(user_id: &str) -> Result<Profile, String> {
-    let client = DynamoDbClient::new(Region::UsEast1);
-    let mut get_item_input: GetItemInput = Default::default();
-    let mut key: HashMap<String, AttributeValue> = HashMap::new();
-    let mut key_val: AttributeValue = Default::default();
-    key_val.s = Some(user_id.to_string());
-
-    key.insert("discord_id".to_string(), key_val);
-    get_item_input.key = key;
-    get_item_input.table_name = "TPCMemberPoints".to_string();
-
-    match client.get_item(get_item_input).await {
-        Ok(output) => 
-            match output.item {
-                Some(item) => {
-                    let points = item["points"].n.as_ref().unwrap_or(&"0".to_string()).parse::<i64>().unwrap_or(0);
-                    let credits = item["credits"].n.as_ref().unwrap_or(&"0".to_string()).parse::<i64>().unwrap_or(0);
-
-                    Ok(Profile { points, credits })
-                }
-                None => {
-                    Ok(Profile { points: 0, credits: 0 })
-                }
-        },
-        Err(err) =>
-            Err(err.to_string())
-    }
+#[command]
+async fn leaderboard(ctx: &Context, msg: &Message) -> CommandResult {
+    msg.channel_id.broadcast_typing(&ctx).await?;
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = points::leaderboard(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-fn show_points(profile: Profile) -> String {
-    format!("{} :star:\n{} :gem:", profile.points, profile.credits)
+#[command]
+async fn givepoints(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = points::givepoints(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-async fn set_profile(user_id: &str, profile: Profile) -> Result<Profile, String> {
-    let client = DynamoDbClient::new(Region::UsEast1);
-    let mut put_item_input : PutItemInput = Default::default();
-    let mut new_item: HashMap<String, AttributeValue> = HashMap::new();
-    let mut key: AttributeValue = Default::default();
-    key.s = Some(user_id.to_string());
-
-    let mut points_attr: AttributeValue = Default::default();
-    points_attr.n = Some(profile.points.to_string());
-
-    let mut credits_attr: AttributeValue = Default::default();
-    credits_attr.n = Some(profile.credits.to_string());
-
-    new_item.insert("discord_id".to_string(), key);
-    new_item.insert("points".to_string(), points_attr);
-    new_item.insert("credits".to_string(), credits_attr);
-    put_item_input.item = new_item;
-    put_item_input.table_name = "TPCMemberPoints".to_string();
-
-    match client.put_item(put_item_input).await {
-        Ok(_) => {
-            Ok(profile)
-        },
-        Err(err) =>
-            Err(err.to_string())
-        
-    }
+#[command]
+async fn givegems(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = points::givegems(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
 #[command]
-async fn givepoints(ctx: &Context, msg: &Message) -> CommandResult {
-
-    if !message_from_admin(msg){
-        return Ok(())
-    }
-
+async fn transfer(ctx: &Context, msg: &Message) -> CommandResult {
     msg.channel_id.broadcast_typing(&ctx).await?;
-
-    //get args
-    let mut content = msg.content.to_string();
-    content.remove(0);
-    let sections: Vec<&str> = content.split_ascii_whitespace().collect();
-    let mut user_id = sections[1].to_string();
-    if msg.mentions.len() > 0 {
-        user_id = msg.mentions[0].id.to_string();
-    };
-    let amt = sections[2].parse::<i64>().unwrap();
-
-    match get_profile(&user_id).await {
-        Ok(profile) => {
-            let new_points = profile.points + amt;
-            match set_profile(&user_id, Profile {points: new_points, credits: profile.credits }).await {
-                Ok(new_profile) => {
-                    msg.channel_id.send_message(&ctx, |m| {
-                        m.content("");
-                        m.embed(|e| {
-                            e.title("Given points!");
-                            e.description(show_points(new_profile));
-
-                            e
-                        });
-                        m
-                    }).await?;
-                },
-                Err(err) => {
-                    println!("Error: {:?}", err);
-                }
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let context = invocation.context();
+    let reply = match points::transfer(&context, &invocation.args).await? {
+        Transfer::Refused(reply) => reply,
+        Transfer::NeedsConfirmation { transfer, prompt } => {
+            if confirm::confirm(ctx, msg, &prompt).await? {
+                points::confirm_transfer(&context, transfer).await?
+            } else {
+                points::cancelled_transfer()
             }
-        },
-        Err(err) => {
-            println!("Error: {:?}", err);
         }
     };
-
+    send_reply(ctx, msg, reply).await?;
     Ok(())
 }
 
-fn message_from_admin(msg: &Message) -> bool {
-    match msg.member.to_owned() {
-        None => false,
-        Some(member) => {
-            member.roles.contains(&RoleId(449076533223751691)) ||
-             member.roles.contains(&RoleId(778454540814909472))
-
-        }
-    }
-
+#[command]
+async fn audit(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = points::audit(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
 #[command]
-async fn givegems(ctx: &Context, msg: &Message) -> CommandResult {
-
-    if !message_from_admin(msg){
-        return Ok(())
+async fn store(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let context = invocation.context();
+    let pages = store::store_pages(&context).await?;
+    if pages.is_empty() {
+        send_reply(ctx, msg, store::empty_store()).await?;
+        return Ok(());
     }
 
-    msg.channel_id.broadcast_typing(&ctx).await?;
-
-    //get args
-    let mut content = msg.content.to_string();
-    content.remove(0);
-    let sections: Vec<&str> = content.split_ascii_whitespace().collect();
-    match (sections.get(1), sections.get(2).and_then(|amt| amt.parse::<i64>().ok())){
-         (Some(user_id), Some(amt)) => {
-            match get_profile(&user_id).await {
-                Ok(profile) => {
-                    let new_credits = profile.credits + amt;
-                    match set_profile(&user_id, Profile {points: profile.points, credits: new_credits }).await {
-                        Ok(new_profile) => {
-                            msg.channel_id.send_message(&ctx, |m| {
-                                m.content("");
-                                m.embed(|e| {
-                                    e.title("Given gems!");
-                                    e.description(show_points(new_profile));
-
-                                    e
-                                });
-                                m
-                            }).await?;
-                        },
-                        Err(err) => {
-                            println!("Error: {:?}", err);
-                        }
-                    }
-                },
-                Err(err) => {
-                    println!("Error: {:?}", err);
-                }
-            };
-
-            Ok(())
-        },
-        _ => Ok(())
-    }
+    let state = StoreState::new(pages);
+    let colour = config::get(ctx).await.colour();
+    let message = msg.channel_id.send_message(&ctx, |m| {
+        m.embed(|e| state.embed(e, colour)).components(|c| state.components(c))
+    }).await?;
+    store_view::browse(ctx, &context, message, msg.author.id, state).await?;
+    Ok(())
 }
 
-struct Product {
-  name: String,
-  price: i64,
-  quantity: i64,
-  key: String,
-  description: String
+#[command]
+async fn addproduct(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = store::addproduct(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-fn item_to_product(item: &HashMap<String, AttributeValue>) -> Product {
-    let empty_str : String = "".to_string();
-    let name = item["name"].s.as_ref().unwrap_or(&empty_str);
-    let price = item["price"].n.as_ref().unwrap_or(&"0".to_string()).parse::<i64>().unwrap_or(0);
-    let quantity = item["quantity"].n.as_ref().unwrap_or(&"0".to_string()).parse::<i64>().unwrap_or(0);
-    let key = item["key"].s.as_ref().unwrap_or(&empty_str);
-    let description = item["description"].s.as_ref().unwrap_or(&empty_str);
-    Product { name: name.to_string(), price, key: key.to_string(), description: description.to_string(), quantity }
+#[command]
+async fn editproduct(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = store::editproduct(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-async fn get_store() -> Result<Vec<Product>,String> {
-    let client = DynamoDbClient::new(Region::UsEast1);
-    let mut scan_input: ScanInput = Default::default();
-
-    scan_input.table_name = "TPCStore".to_string();
-
-    match client.scan(scan_input).await {
-        Ok(output) => 
-            match output.items {
-                Some(items) => {
-                    let products = items.iter().map(item_to_product).collect();
-                    Ok(products)
-                }
-                None => {
-                    Ok(Vec::new())
-                }
-        },
-        Err(err) =>
-            Err(err.to_string())
-    }
+#[command]
+async fn restock(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = store::restock(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-async fn get_product(product_key: &str) -> Result<Option<Product>, String> {
-    let client = DynamoDbClient::new(Region::UsEast1);
-    let mut get_item_input: GetItemInput = Default::default();
-    let mut key: HashMap<String, AttributeValue> = HashMap::new();
-
-    key.insert("key".to_string(), string_attr(&product_key.to_string()));
-    get_item_input.key = key;
-    get_item_input.table_name = "TPCStore".to_string();
-
-    match client.get_item(get_item_input).await {
-        Ok(output) => 
-            match output.item {
-                Some(item) => {
-                    Ok(Some(item_to_product(&item)))
-                }
-                None => {
-                    Ok(None)
-                }
-        },
-        Err(err) =>
-            Err(err.to_string())
+#[command]
+async fn addcodes(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let context = invocation.context();
+    // The codes were typed where others can see them, so they're only kept once they're taken
+    // down again; that needs Manage Messages, and /addcodes doesn't show them to anyone at all
+    if context.caller.is_admin && msg.delete(ctx).await.is_err() {
+        let response = Response::error("Codes not added", "I couldn't delete your message, so others may have seen the codes. Use /addcodes instead, with codes nobody has seen");
+        send_reply(ctx, msg, response.into()).await?;
+        return Ok(());
     }
+    let reply = store::addcodes(&context, &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-fn string_attr(string: &String) -> AttributeValue {
-    let mut attr: AttributeValue = Default::default();
-    attr.s = Some(string.to_string());
-    attr
+#[command]
+async fn delproduct(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = store::delproduct(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-fn number_attr(number: &i64) -> AttributeValue {
-    let mut attr: AttributeValue = Default::default();
-    attr.n = Some(number.to_string());
-    attr
+#[command]
+async fn buy(ctx: &Context, msg: &Message) -> CommandResult {
+    msg.channel_id.broadcast_typing(&ctx).await?;
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = store::buy(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-async fn delete_product(key: &str) -> Result<String,String> {
-    let client = DynamoDbClient::new(Region::UsEast1);
-    let mut delete_item_input: DeleteItemInput = Default::default();
-    
-    let mut delete_key: HashMap<String, AttributeValue> = HashMap::new();
-    delete_key.insert("key".to_string(), string_attr(&key.to_string()));
-
-    delete_item_input.table_name = "TPCStore".to_string();
-    delete_item_input.key = delete_key;
-
-    match client.delete_item(delete_item_input).await {
-        Ok(_) => Ok(key.to_string()),
-        Err(err) => Err(err.to_string())
-    }
+#[command]
+async fn mypurchases(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = orders::mypurchases(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-async fn put_product(product: Product) -> Result<Product,String> {
-    let client = DynamoDbClient::new(Region::UsEast1);
-    let mut put_item_input: PutItemInput = Default::default();
-    
-    let mut new_item: HashMap<String, AttributeValue> = HashMap::new();
-
-    new_item.insert("key".to_string(), string_attr(&product.key));
-    new_item.insert("name".to_string(), string_attr(&product.name));
-    new_item.insert("description".to_string(), string_attr(&product.description));
-    new_item.insert("price".to_string(), number_attr(&product.price));
-    new_item.insert("quantity".to_string(), number_attr(&product.quantity));
-
-    put_item_input.table_name = "TPCStore".to_string();
-    put_item_input.item = new_item;
-
-    match client.put_item(put_item_input).await {
-        Ok(_) => 
-            Ok(product)
-        ,
-        Err(err) =>
-            Err(err.to_string())
-    }
+#[command]
+async fn orders(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = orders::orders(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-fn show_product(product: &Product) -> String {
-    format!("`{}`: **{}** ({} :gem:, {} left)\n{}",product.key, product.name, product.price, product.quantity, product.description)
+#[command]
+async fn fulfil(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = orders::fulfil(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
 #[command]
-async fn store(ctx: &Context, msg: &Message) -> CommandResult {
-
-    //get args
-    let mut content = msg.content.to_string();
-    content.remove(0);
-    match get_store().await {
-        Ok(products) => {
-            msg.channel_id.send_message(&ctx, |m| {
-                m.content("");
-                m.embed(|e| {
-                    e.title("Store: ");
-                    let product_lines : Vec<String> = products.iter().map(|product| {
-                        show_product(product)
-                    }).collect();
-                    let message : String = product_lines.join("\n\n");
-                    e.description(message);
-                    e
-                });
-                m
-            }).await?;
-        },
-        Err(err) => {
-            println!("Error: {:?}", err);
-        }
-    };
+async fn cancel(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = orders::cancel(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
     Ok(())
 }
 
 #[command]
-async fn addproduct(ctx: &Context, msg: &Message) -> CommandResult {
-
-    if !message_from_admin(msg){
-        return Ok(())
-    }
-    msg.channel_id.broadcast_typing(&ctx).await?;
-
-    //get args
-    let mut content = msg.content.to_string();
-    content.remove(0);
-    let sections: Vec<String> = split(&content).ok().unwrap();
-    match ( sections.get(1)
-          , sections.get(2)
-          , sections.get(3)
-          , sections.get(4).and_then(|x| x.parse::<i64>().ok())
-          , sections.get(5).and_then(|x| x.parse::<i64>().ok())
-          ) {
-        (Some(key), Some(name), Some(description), Some(price), Some(quantity)) => {
-            match put_product(Product { key: key.to_string(), name: name.to_string(), description: description.to_string(), price, quantity }).await {
-                Ok(product) => {
-                    msg.channel_id.send_message(&ctx, |m| {
-                        m.content("");
-                        m.embed(|e| {
-                            e.title("Added Product");
-                            let message = show_product(&product);
-                            e.description(message);
-                            e
-                        });
-                        m
-                    }).await?;
-                },
-                Err(err) => {
-                    println!("Error: {:?}", err);
-                }
-            }
-        },
-        _ => {}
-    };
+async fn refund(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = orders::refund(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
     Ok(())
 }
 
 #[command]
-async fn delproduct(ctx: &Context, msg: &Message) -> CommandResult {
+async fn activities(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = activities::activities(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
+}
 
-    if !message_from_admin(msg){
-        return Ok(())
+#[command]
+async fn claim(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    // Attachments are evidence too, kept as links to them
+    let mut args = invocation.args.clone();
+    args.extend(msg.attachments.iter().map(|attachment| attachment.url.clone()));
+    match claims::claim(&invocation.context(), &args).await? {
+        Submission::Refused(reply) => send_reply(ctx, msg, reply).await?,
+        Submission::Submitted { claim_id, reply, review } => {
+            send_reply(ctx, msg, reply.into()).await?;
+            review::post(ctx, &claim_id, &review).await?;
+        }
     }
-    msg.channel_id.broadcast_typing(&ctx).await?;
-
-    //get args
-    let content = msg.content.to_string();
-    let sections: Vec<String> = split(&content).ok().unwrap();
-    match sections.get(1) {
-        Some(key) => {
-            match delete_product(&key).await {
-                Ok(_) => {
-                    msg.channel_id.send_message(&ctx, |m| {
-                        m.content("");
-                        m.embed(|e| {
-                            e.title("Deleted Product");
-                            let message = format!("Deleted product {}", key);
-                            e.description(message);
-                            e
-                        });
-                        m
-                    }).await?;
-                },
-                Err(err) => {
-                    println!("Error: {:?}", err);
-                }
-            }
-        },
-        _ => {}
-    };
     Ok(())
 }
 
 #[command]
-async fn buy(ctx: &Context, msg: &Message) -> CommandResult {
+async fn approve(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = claims::approve(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
+}
 
-    //get args
-    let content = msg.content.to_string();
+#[command]
+async fn reject(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = claims::reject(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
+}
 
-    msg.channel_id.broadcast_typing(&ctx).await?;
-    let sections: Vec<String> = split(&content).ok().unwrap();
-    match sections.get(1) {
-        Some(key) => {
-            let profile = get_profile(&msg.author.id.to_string()).await.ok();
-            let product = get_product(&key).await.ok();
-            match (profile, product) {
-                (Some(profile), Some(Some(product))) => {
-                    if profile.credits >= product.price {
-                        if product.quantity > 0 {
-                            let new_credits = profile.credits - product.price;
-                            let new_quantity = product.quantity - 1;
-                            let uuid = Uuid::new_v4().to_string();
-                            let new_purchase = Purchase { id: uuid, product_key: key.to_string(), discord_id: msg.author.id.to_string() };
-                            add_purchase(new_purchase).await?;
-
-                            let new_product = put_product(Product { name: product.name
-                                                , description: product.description
-                                                , key: product.key
-                                                , price: product.price
-                                                , quantity: new_quantity
-                                                }
-                                        ).await?;
-
-                            let new_profile = set_profile( &msg.author.id.to_string()
-                                       , Profile { points: profile.points
-                                                , credits: new_credits
-                                                }
-                                                ).await?;
-
-                            msg.channel_id.send_message(&ctx, |m| {
-                                m.content("");
-                                m.embed(|e| {
-                                    e.title("Purchase successful");
-                                    e.description(format!("You just purchased a {}\nYou have {} :gem: left", new_product.name, new_profile.credits));
-                                    e
-                                });
-                                m
-                            }).await?;
-
-                        }
-                        else{
-                            msg.channel_id.send_message(&ctx, |m| {
-                                m.content("");
-                                m.embed(|e| {
-                                    e.title("Out of stock");
-                                    e.description(format!("Sorry, we don't have any more of: {}", product.name));
-                                    e
-                                });
-                                m
-                            }).await?;
-                        }
-                    }
-                    else {
-                        msg.channel_id.send_message(&ctx, |m| {
-                            m.content("");
-                            m.embed(|e| {
-                                e.title("You can't afford that!");
-                                e.description(format!("You only have {} :gem:, but \"{}\" costs {} :gem:", profile.credits, product.name, product.price));
-                                e
-                            });
-                            m
-                        }).await?;
-                    }
-                }
-                _ => {
-                    send_embed(&msg.channel_id, &ctx, "Cannot find product", "Could not find the product you are refering to").await?;
-                }
-            }
-        },
-        _ => {
-            send_embed(&msg.channel_id, &ctx, "Usage", "~buy [product_id]").await?;
-        }
-    };
+#[command]
+async fn addevent(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = events::addevent(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
     Ok(())
 }
 
-async fn send_embed(channel_id: &ChannelId,ctx: &Context, title: &str, content: &str) -> Result<(), Error>{
-    channel_id.send_message(&ctx, |m| {
-        m.content("");
-        m.embed(|e| {
-            e.title(title);
-            e.description(content);
-            e
-        });
-        m
-    }).await?;
+#[command]
+async fn checkin(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = events::checkin(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
     Ok(())
 }
 
-struct Purchase {
-  id: String,
-  product_key: String,
-  discord_id: String
+#[command]
+async fn attendance(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = events::attendance(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
-async fn add_purchase(purchase: Purchase) -> Result<Purchase, String>{
-    let client = DynamoDbClient::new(Region::UsEast1);
-    let mut put_item_input: PutItemInput = Default::default();
-    
-    let mut new_item: HashMap<String, AttributeValue> = HashMap::new();
-
-    new_item.insert("id".to_string(), string_attr(&purchase.id));
-    new_item.insert("product_key".to_string(), string_attr(&purchase.product_key));
-    new_item.insert("discord_id".to_string(), string_attr(&purchase.discord_id));
-
-    put_item_input.table_name = "TPCPurchases".to_string();
-    put_item_input.item = new_item;
-
-    match client.put_item(put_item_input).await {
-        Ok(_) => 
-            Ok(purchase)
-        ,
-        Err(err) =>
-            Err(err.to_string())
-    }
+#[command]
+async fn addattendance(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = events::addattendance(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
+    Ok(())
 }
 
 #[command]
-async fn activities(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut file = File::open("activities.txt")?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
-    send_embed(&msg.channel_id, &ctx, "Current Activities", &contents).await?;
+async fn syncroles(ctx: &Context, msg: &Message) -> CommandResult {
+    let invocation = Invocation::from_message(ctx, msg).await;
+    let reply = tiers::syncroles(&invocation.context(), &invocation.args).await?;
+    send_reply(ctx, msg, reply).await?;
     Ok(())
 }
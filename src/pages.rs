@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use serenity::builder::CreateEmbed;
+use serenity::client::Context;
+use serenity::model::channel::{Message, ReactionType};
+use serenity::model::id::UserId;
+use serenity::Error;
+
+use leadershipdiscordbot_rs::commands::Response;
+use leadershipdiscordbot_rs::config;
+
+use crate::reply;
+
+const PREVIOUS: char = '◀';
+const NEXT: char = '▶';
+
+/// How long the author can keep flipping pages after the last reaction.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Sends an embed showing the first page, with reactions the author of `msg`
+/// can use to flip between pages until they stop for a while.
+pub async fn send_pages(ctx: &Context, msg: &Message, pages: &[Response]) -> Result<(), Error> {
+    let colour = config::get(ctx).await.colour();
+    let reply = msg.channel_id.send_message(&ctx, |m| {
+        m.content("");
+        m.embed(|e| page(e, pages, 0, colour));
+        m
+    }).await?;
+    flip_pages(ctx, reply, msg.author.id, pages).await
+}
+
+/// Fills in `e` with page `index` of `pages` and a footer saying where it is.
+pub fn page<'a>(e: &'a mut CreateEmbed, pages: &[Response], index: usize, colour: u64) -> &'a mut CreateEmbed {
+    let empty = Response::new("", "Nothing to show");
+    reply::embed(e, pages.get(index).unwrap_or(&empty), colour);
+    e.footer(|f| f.text(format!("Page {}/{}", index + 1, pages.len().max(1))));
+    e
+}
+
+/// Adds reactions to `reply`, which already shows the first page, so `author_id`
+/// can flip between pages until they stop for a while.
+pub async fn flip_pages(ctx: &Context, mut reply: Message, author_id: UserId, pages: &[Response]) -> Result<(), Error> {
+    if pages.len() <= 1 {
+        return Ok(());
+    }
+
+    let colour = config::get(ctx).await.colour();
+    let mut index = 0;
+    reply.react(&ctx, PREVIOUS).await?;
+    reply.react(&ctx, NEXT).await?;
+
+    // Removing a reaction counts as a click too, so the bot doesn't need permission to clear them
+    while let Some(action) = reply.await_reaction(ctx)
+        .author_id(author_id)
+        .added(true)
+        .removed(true)
+        .timeout(PAGE_TIMEOUT)
+        .await {
+        let emoji = &action.as_inner_ref().emoji;
+        if *emoji == ReactionType::from(PREVIOUS) {
+            index = (index + pages.len() - 1) % pages.len();
+        }
+        else if *emoji == ReactionType::from(NEXT) {
+            index = (index + 1) % pages.len();
+        }
+        else {
+            continue;
+        }
+
+        reply.edit(&ctx, |m| m.embed(|e| page(e, pages, index, colour))).await?;
+    }
+    Ok(())
+}
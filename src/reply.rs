@@ -0,0 +1,54 @@
+use std::borrow::Cow;
+
+use serenity::builder::CreateEmbed;
+use serenity::client::Context;
+use serenity::model::channel::{AttachmentType, Message};
+use serenity::Error;
+
+use leadershipdiscordbot_rs::commands::{Attachment, Reply, Response};
+use leadershipdiscordbot_rs::config;
+
+use crate::pages;
+
+/// Fills in `e` from a response, falling back to `colour` if it doesn't have its own.
+pub fn embed<'a>(e: &'a mut CreateEmbed, response: &Response, colour: u64) -> &'a mut CreateEmbed {
+    e.title(&response.title);
+    e.description(&response.description);
+    e.color(response.colour.unwrap_or(colour));
+    for field in &response.fields {
+        e.field(&field.name, &field.value, field.inline);
+    }
+    e
+}
+
+/// Sends whatever a command replied with to the channel `msg` came from.
+pub async fn send_reply(ctx: &Context, msg: &Message, reply: Reply) -> Result<(), Error> {
+    match reply {
+        Reply::Nothing => Ok(()),
+        Reply::Embed(response) => {
+            let colour = config::get(ctx).await.colour();
+            msg.channel_id.send_message(&ctx, |m| {
+                m.content("");
+                m.embed(|e| embed(e, &response, colour));
+                m
+            }).await?;
+            Ok(())
+        },
+        Reply::Pages(responses) => pages::send_pages(ctx, msg, &responses).await,
+        Reply::File(response, file) => {
+            let colour = config::get(ctx).await.colour();
+            msg.channel_id.send_message(&ctx, |m| {
+                m.content("");
+                m.embed(|e| embed(e, &response, colour));
+                m.add_file(attachment(file));
+                m
+            }).await?;
+            Ok(())
+        }
+    }
+}
+
+/// A file from a reply, ready to send with a message or interaction response.
+pub fn attachment(file: Attachment) -> AttachmentType<'static> {
+    AttachmentType::Bytes { data: Cow::from(file.contents.into_bytes()), filename: file.filename }
+}
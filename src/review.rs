@@ -0,0 +1,83 @@
+use serenity::builder::CreateEmbed;
+use serenity::client::Context;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::id::ChannelId;
+use serenity::Error;
+
+use leadershipdiscordbot_rs::commands::{claims, Reply, Response, ERROR_COLOUR};
+use leadershipdiscordbot_rs::config;
+
+use crate::invocation::Invocation;
+use crate::reply;
+
+/// Review buttons carry the claim's id after these, so they keep working after a restart.
+const APPROVE: &str = "claim_approve:";
+const REJECT: &str = "claim_reject:";
+
+/// Posts a claim to the review channel with buttons to approve or reject it. Without a
+/// review channel configured this does nothing, and admins approve or reject by id instead.
+pub async fn post(ctx: &Context, claim_id: &str, review: &Response) -> Result<(), Error> {
+    let config = config::get(ctx).await;
+    let channel = match config.review_channel {
+        Some(channel) => ChannelId(channel),
+        None => return Ok(())
+    };
+
+    channel.send_message(&ctx, |m| {
+        m.embed(|e| reply::embed(e, review, config.colour()))
+            .components(|c| c.create_action_row(|row| {
+                row.create_button(|b| b.custom_id(format!("{}{}", APPROVE, claim_id)).label("Approve").style(ButtonStyle::Success))
+                    .create_button(|b| b.custom_id(format!("{}{}", REJECT, claim_id)).label("Reject").style(ButtonStyle::Danger))
+            }))
+    }).await?;
+    Ok(())
+}
+
+/// Handles a click on a review button; clicks on anything else are left to whoever is collecting them.
+pub async fn handle(ctx: &Context, component: &MessageComponentInteraction) -> Result<(), Error> {
+    let custom_id = &component.data.custom_id;
+    let (approving, claim_id) = match (custom_id.strip_prefix(APPROVE), custom_id.strip_prefix(REJECT)) {
+        (Some(claim_id), _) => (true, claim_id),
+        (_, Some(claim_id)) => (false, claim_id),
+        _ => return Ok(())
+    };
+
+    let invocation = Invocation::from_component(ctx, component, vec![claim_id.to_string()]).await;
+    let context = invocation.context();
+    let result = if approving {
+        claims::approve(&context, &invocation.args).await
+    } else {
+        claims::reject(&context, &invocation.args).await
+    };
+    let reply = result.unwrap_or_else(|err| {
+        println!("Error reviewing claim {}: {:?}", claim_id, err);
+        Response::error("Something went wrong", "Please try again later").into()
+    });
+
+    let colour = config::get(ctx).await.colour();
+    match reply {
+        // Keep the claim on the message, with the outcome in place of the buttons
+        Reply::Embed(response) if response.colour != Some(ERROR_COLOUR) => {
+            let mut embed = component.message.embeds.first().cloned().map(CreateEmbed::from).unwrap_or_default();
+            embed.field(&response.title, &response.description, false);
+            component.create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| d.set_embed(embed).components(|c| c))
+            }).await
+        },
+        Reply::Embed(response) => {
+            component.create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| d.ephemeral(true).embed(|e| reply::embed(e, &response, colour)))
+            }).await
+        },
+        _ => {
+            component.create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| d.ephemeral(true).content("Only admins can review claims"))
+            }).await
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use serenity::builder::CreateApplicationCommands;
+use serenity::client::Context;
+use serenity::model::application::command::CommandOptionType;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue};
+use serenity::model::application::interaction::autocomplete::AutocompleteInteraction;
+use serenity::Error;
+
+use leadershipdiscordbot_rs::commands::{activities, events, orders, points, store, CommandContext, Reply, Response};
+use leadershipdiscordbot_rs::{config, storage};
+
+use crate::invocation::Invocation;
+use crate::{pages, reply, store_view};
+use crate::store_view::StoreState;
+
+struct SlashCommand {
+    name: &'static str,
+    description: &'static str,
+    /// In the order the prefix command takes them as arguments
+    options: &'static [SlashOption]
+}
+
+struct SlashOption {
+    name: &'static str,
+    description: &'static str,
+    kind: CommandOptionType,
+    required: bool,
+    /// Suggest product keys as this is typed
+    products: bool
+}
+
+const fn option(name: &'static str, description: &'static str, kind: CommandOptionType, required: bool) -> SlashOption {
+    SlashOption { name, description, kind, required, products: false }
+}
+
+const fn product_option(name: &'static str, description: &'static str) -> SlashOption {
+    SlashOption { name, description, kind: CommandOptionType::String, required: true, products: true }
+}
+
+/// The prefix commands that can also be run as slash commands.
+const SLASH_COMMANDS: &[SlashCommand] = &[
+    SlashCommand {
+        name: "getpoints",
+        description: "Show a member's points and gems",
+        options: &[option("user", "Whose points to show, or yours if left out", CommandOptionType::User, false)]
+    },
+    SlashCommand {
+        name: "givepoints",
+        description: "Give points to a member or everyone with a role (admins only)",
+        options: &[
+            option("user", "Member to give points to", CommandOptionType::User, false),
+            option("role", "Give points to everyone with this role", CommandOptionType::Role, false),
+            option("amount", "Points to give; negative takes them away", CommandOptionType::Integer, true),
+            option("reason", "What the points are for", CommandOptionType::String, false)
+        ]
+    },
+    SlashCommand {
+        name: "givegems",
+        description: "Give gems to a member (admins only)",
+        options: &[
+            option("user", "Member to give gems to", CommandOptionType::User, true),
+            option("amount", "Gems to give; negative takes them away", CommandOptionType::Integer, true),
+            option("reason", "What the gems are for", CommandOptionType::String, false)
+        ]
+    },
+    SlashCommand {
+        name: "store",
+        description: "List what's for sale",
+        options: &[]
+    },
+    SlashCommand {
+        name: "buy",
+        description: "Buy something from the store with gems",
+        options: &[product_option("product", "Product to buy")]
+    },
+    SlashCommand {
+        name: "mypurchases",
+        description: "List everything you've bought",
+        options: &[]
+    },
+    SlashCommand {
+        name: "addproduct",
+        description: "Add a product to the store, or replace one not sold as codes (admins only)",
+        options: &[
+            option("key", "Short name used to buy it", CommandOptionType::String, true),
+            option("name", "Name shown in the store", CommandOptionType::String, true),
+            option("description", "Description shown in the store", CommandOptionType::String, true),
+            option("price", "Price in gems", CommandOptionType::Integer, true),
+            option("quantity", "How many are in stock", CommandOptionType::Integer, true)
+        ]
+    },
+    SlashCommand {
+        name: "restock",
+        description: "Add stock to a product (admins only)",
+        options: &[
+            product_option("product", "Product to restock"),
+            option("amount", "How many more are in stock", CommandOptionType::Integer, true)
+        ]
+    },
+    SlashCommand {
+        name: "addcodes",
+        description: "Add codes to sell a product as, one each (admins only)",
+        options: &[
+            product_option("product", "Product the codes are for"),
+            option("codes", "Codes to add, separated by spaces", CommandOptionType::String, true)
+        ]
+    },
+    SlashCommand {
+        name: "delproduct",
+        description: "Remove a product from the store (admins only)",
+        options: &[product_option("product", "Product to remove")]
+    },
+    SlashCommand {
+        name: "activities",
+        description: "List the activities you can earn points for",
+        options: &[]
+    },
+    SlashCommand {
+        name: "checkin",
+        description: "Check in to an event you're at for its points",
+        options: &[option("code", "The check-in code for the event", CommandOptionType::String, true)]
+    },
+    SlashCommand {
+        name: "attendance",
+        description: "Export who was at an event, or a report on every event (admins only)",
+        options: &[option("event", "Event id, or leave out for every event", CommandOptionType::String, false)]
+    }
+];
+
+/// Commands whose responses only the caller sees, so nobody else sees what they were given.
+const PRIVATE_COMMANDS: &[&str] = &["addcodes"];
+
+/// Adds every slash command, to be set on each guild the bot is in.
+pub fn register(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    for slash_command in SLASH_COMMANDS {
+        commands.create_application_command(|c| {
+            c.name(slash_command.name).description(slash_command.description);
+            for slash_option in slash_command.options {
+                c.create_option(|o| {
+                    o.name(slash_option.name)
+                        .description(slash_option.description)
+                        .kind(slash_option.kind)
+                        .required(slash_option.required)
+                        .set_autocomplete(slash_option.products)
+                });
+            }
+            c
+        });
+    }
+    commands
+}
+
+/// The command's options as the arguments its prefix command would get.
+fn args(command: &ApplicationCommandInteraction) -> Vec<String> {
+    let slash_command = match SLASH_COMMANDS.iter().find(|slash_command| slash_command.name == command.data.name) {
+        Some(slash_command) => slash_command,
+        None => return Vec::new()
+    };
+
+    slash_command.options.iter()
+        .filter_map(|slash_option| command.data.options.iter().find(|option| option.name == slash_option.name))
+        .filter_map(|option| match option.resolved.as_ref()? {
+            CommandDataOptionValue::User(user, _) => Some(format!("<@{}>", user.id)),
+            CommandDataOptionValue::Role(role) => Some(format!("<@&{}>", role.id)),
+            CommandDataOptionValue::Integer(value) => Some(value.to_string()),
+            CommandDataOptionValue::String(value) => Some(value.clone()),
+            _ => None
+        })
+        .collect()
+}
+
+/// Runs a slash command and responds to it.
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<(), Error> {
+    let invocation = Invocation::from_command(ctx, command, args(command)).await;
+    let context = invocation.context();
+    let args = &invocation.args;
+    if command.data.name == "store" {
+        return open_store(ctx, command, &context).await;
+    }
+
+    let reply = match command.data.name.as_str() {
+        "getpoints" => points::getpoints(&context, args).await,
+        "givepoints" => points::givepoints(&context, args).await,
+        "givegems" => points::givegems(&context, args).await,
+        "buy" => store::buy(&context, args).await,
+        "mypurchases" => orders::mypurchases(&context, args).await,
+        "addproduct" => store::addproduct(&context, args).await,
+        "restock" => store::restock(&context, args).await,
+        "addcodes" => store::addcodes(&context, &split_codes(args)).await,
+        "delproduct" => store::delproduct(&context, args).await,
+        "activities" => activities::activities(&context, args).await,
+        "checkin" => events::checkin(&context, args).await,
+        "attendance" => events::attendance(&context, args).await,
+        _ => return Ok(())
+    };
+
+    let reply = reply.unwrap_or_else(|err| {
+        println!("Error in /{}: {:?}", command.data.name, err);
+        Response::error("Something went wrong", "Please try again later").into()
+    });
+    respond(ctx, command, reply).await
+}
+
+/// All the codes for `/addcodes` come in one option, rather than an argument each.
+fn split_codes(args: &[String]) -> Vec<String> {
+    args.iter().flat_map(|arg| arg.split_whitespace().map(str::to_string)).collect()
+}
+
+async fn respond(ctx: &Context, command: &ApplicationCommandInteraction, reply: Reply) -> Result<(), Error> {
+    let colour = config::get(ctx).await.colour();
+    let private = PRIVATE_COMMANDS.contains(&command.data.name.as_str());
+    match reply {
+        // Slash commands always need an answer, but only the caller needs to see this one
+        Reply::Nothing => {
+            command.create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| d.ephemeral(true).content("Only admins can use this command"))
+            }).await
+        },
+        Reply::Embed(response) => {
+            command.create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| d.ephemeral(private).embed(|e| reply::embed(e, &response, colour)))
+            }).await
+        },
+        Reply::Pages(responses) => {
+            command.create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| d.embed(|e| pages::page(e, &responses, 0, colour)))
+            }).await?;
+            let message = command.get_interaction_response(&ctx.http).await?;
+            pages::flip_pages(ctx, message, command.user.id, &responses).await
+        },
+        Reply::File(response, file) => {
+            command.create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| d.embed(|e| reply::embed(e, &response, colour)).add_file(reply::attachment(file)))
+            }).await
+        }
+    }
+}
+
+/// Shows the store to whoever ran /store and lets them browse it.
+async fn open_store(ctx: &Context, command: &ApplicationCommandInteraction, context: &CommandContext<'_>) -> Result<(), Error> {
+    let pages = match store::store_pages(context).await {
+        Ok(pages) if pages.is_empty() => return respond(ctx, command, store::empty_store()).await,
+        Ok(pages) => pages,
+        Err(err) => {
+            println!("Error in /store: {:?}", err);
+            return respond(ctx, command, Response::error("Something went wrong", "Please try again later").into()).await;
+        }
+    };
+
+    let state = StoreState::new(pages);
+    let colour = config::get(ctx).await.colour();
+    command.create_interaction_response(&ctx.http, |r| {
+        r.kind(InteractionResponseType::ChannelMessageWithSource)
+            .interaction_response_data(|d| d.embed(|e| state.embed(e, colour)).components(|c| state.components(c)))
+    }).await?;
+    let message = command.get_interaction_response(&ctx.http).await?;
+    store_view::browse(ctx, context, message, command.user.id, state).await
+}
+
+/// Suggests products for whichever product option is being typed.
+pub async fn autocomplete(ctx: &Context, autocomplete: &AutocompleteInteraction) -> Result<(), Error> {
+    let typed = autocomplete.data.options.iter()
+        .find(|option| option.focused)
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_str())
+        .unwrap_or("");
+
+    let storage = storage::get(ctx).await;
+    let choices = store::product_choices(&*storage, typed).await.unwrap_or_else(|err| {
+        println!("Error suggesting products: {:?}", err);
+        Vec::new()
+    });
+
+    autocomplete.create_autocomplete_response(&ctx.http, |r| {
+        for (label, key) in choices {
+            r.add_string_choice(label, key);
+        }
+        r
+    }).await
+}
@@ -0,0 +1,1485 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rusoto_core::{Region, RusotoError};
+use rusoto_dynamodb::{DynamoDb, DynamoDbClient, PutItemInput, GetItemInput, AttributeValue, ScanInput, DeleteItemInput,
+                      UpdateItemInput, UpdateItemError,
+                      TransactWriteItemsInput, TransactWriteItemsError, TransactWriteItem, Put, Update, Delete, ConditionCheck, QueryInput,
+                      CreateTableInput, CreateTableError, DescribeTableInput, AttributeDefinition, KeySchemaElement};
+use serenity::async_trait;
+use tokio::time::sleep;
+
+use crate::config::TablesConfig;
+
+use super::{Storage, Attendance, Claim, ClaimStatus, Event, EventOutcome, Profile, Product, ProductChanges, Purchase, PurchaseOutcome, PurchaseStatus, RefundOutcome, TransferOutcome, LedgerEntry, can_pay_back, count_bought, refuse_purchase};
+
+/// How many times to retry a transaction that was cancelled by a concurrent change.
+const TRANSACTION_ATTEMPTS: usize = 3;
+
+/// How many seconds to wait for a new table to be ready before giving up.
+const TABLE_CHECKS: usize = 60;
+
+/// The ledger table is keyed by `discord_id` with `id` as the sort key, so a
+/// member's entries can be queried.
+pub struct DynamoStorage {
+    client: DynamoDbClient,
+    tables: TablesConfig
+}
+
+impl DynamoStorage {
+    pub fn new(region: Region, tables: TablesConfig) -> DynamoStorage {
+        DynamoStorage { client: DynamoDbClient::new(region), tables }
+    }
+
+    /// Creates any of the tables that don't exist yet, whether on DynamoDB Local or ones added
+    /// since the bot was last deployed.
+    pub async fn create_tables(&self) -> Result<(), String> {
+        let tables = vec![
+            (&self.tables.profiles, vec![("discord_id", "HASH")]),
+            (&self.tables.store, vec![("key", "HASH")]),
+            (&self.tables.purchases, vec![("id", "HASH")]),
+            (&self.tables.ledger, vec![("discord_id", "HASH"), ("id", "RANGE")]),
+            (&self.tables.codes, vec![("product_key", "HASH"), ("code", "RANGE")]),
+            (&self.tables.claims, vec![("id", "HASH")]),
+            (&self.tables.events, vec![("id", "HASH")]),
+            (&self.tables.event_codes, vec![("code", "HASH")]),
+            (&self.tables.attendance, vec![("event_id", "HASH"), ("discord_id", "RANGE")])
+        ];
+
+        for (table_name, keys) in tables {
+            let create_table_input = CreateTableInput {
+                table_name: table_name.to_string(),
+                attribute_definitions: keys.iter().map(|(name, _)| AttributeDefinition {
+                    attribute_name: name.to_string(),
+                    attribute_type: "S".to_string()
+                }).collect(),
+                key_schema: keys.iter().map(|(name, key_type)| KeySchemaElement {
+                    attribute_name: name.to_string(),
+                    key_type: key_type.to_string()
+                }).collect(),
+                billing_mode: Some("PAY_PER_REQUEST".to_string()),
+                ..Default::default()
+            };
+
+            match self.client.create_table(create_table_input).await {
+                Ok(_) => self.wait_until_active(table_name).await?,
+                Err(RusotoError::Service(CreateTableError::ResourceInUse(_))) => {},
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits for a table that's just been created, since nothing can be written to it until it's active.
+    async fn wait_until_active(&self, table_name: &str) -> Result<(), String> {
+        for _ in 0..TABLE_CHECKS {
+            let describe_table_input = DescribeTableInput { table_name: table_name.to_string() };
+            match self.client.describe_table(describe_table_input).await {
+                Ok(output) if output.table.as_ref().and_then(|table| table.table_status.as_deref()) == Some("ACTIVE") => return Ok(()),
+                Ok(_) => sleep(Duration::from_secs(1)).await,
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Err(format!("Table {} is still being created, try again in a minute", table_name))
+    }
+
+    /// Refunds or cancels a purchase, depending on `to`.
+    async fn pay_back(&self, id: &str, to: PurchaseStatus, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        for _ in 0..TRANSACTION_ATTEMPTS {
+            let purchase = match self.get_purchase(id).await? {
+                Some(purchase) if !can_pay_back(purchase.status, to) => return Ok(RefundOutcome::AlreadyClosed(purchase)),
+                Some(purchase) if purchase.price_unknown() => return Ok(RefundOutcome::PriceUnknown(purchase)),
+                Some(purchase) => purchase,
+                None => return Ok(RefundOutcome::NoSuchPurchase)
+            };
+            // Codes can't be taken back, so digital products aren't restocked
+            let restock = self.get_product(&purchase.product_key).await?.is_some_and(|product| !product.digital);
+            let bought = match self.get_bought(&purchase.discord_id, &purchase.product_key).await? {
+                Some(count) => Bought::Counted { from: Some(count), to: count - 1 },
+                None => Bought::Uncounted
+            };
+
+            // As with buying, a concurrent change cancels the transaction and we look again
+            let entry = LedgerEntry { discord_id: purchase.discord_id.clone(), points: 0, credits: purchase.price, ..entry.clone() };
+            match self.client.transact_write_items(refund_transaction(&self.tables, &purchase, to, restock, &bought, &entry)).await {
+                Ok(_) => {
+                    let profile = self.get_profile(&purchase.discord_id).await?;
+                    let purchase = Purchase { status: to, ..purchase };
+                    return Ok(RefundOutcome::Refunded { purchase, profile });
+                },
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message)))
+                    if conditions_failed(&message, &[0, 1, 3]) || conflicted(&message) => continue,
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Err("The store is busy, please try again".to_string())
+    }
+
+    /// A member's count of `product_key` bought, if it's being counted. Read consistently,
+    /// since the transaction that follows only goes through if it hasn't moved.
+    async fn get_bought(&self, user_id: &str, product_key: &str) -> Result<Option<i64>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("discord_id".to_string(), string_attr(user_id));
+
+        let get_item_input = GetItemInput {
+            key,
+            table_name: self.tables.profiles.clone(),
+            consistent_read: Some(true),
+            ..Default::default()
+        };
+
+        match self.client.get_item(get_item_input).await {
+            Ok(output) => Ok(output.item
+                .and_then(|item| item.get(&bought_attr(product_key)).and_then(|attr| attr.n.clone()))
+                .and_then(|n| n.parse::<i64>().ok())),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    /// The event that last took a check-in code, if it's still there.
+    async fn get_code_event(&self, code: &str) -> Result<Option<Event>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("code".to_string(), string_attr(code));
+
+        let get_item_input = GetItemInput {
+            key,
+            table_name: self.tables.event_codes.clone(),
+            consistent_read: Some(true),
+            ..Default::default()
+        };
+
+        let event_id = match self.client.get_item(get_item_input).await {
+            Ok(output) => output.item.and_then(|item| item.get("event_id").and_then(|attr| attr.s.clone())),
+            Err(err) => return Err(err.to_string())
+        };
+        match event_id {
+            Some(event_id) => self.get_event(&event_id).await,
+            None => Ok(None)
+        }
+    }
+
+    /// Empties a digital product's pool of unsold codes.
+    async fn delete_codes(&self, product_key: &str) -> Result<(), String> {
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":product_key".to_string(), string_attr(product_key));
+
+        let mut start_key = None;
+        loop {
+            let query_input = QueryInput {
+                table_name: self.tables.codes.clone(),
+                key_condition_expression: Some("product_key = :product_key".to_string()),
+                expression_attribute_values: Some(values.clone()),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            let output = self.client.query(query_input).await.map_err(|err| err.to_string())?;
+            for item in output.items.unwrap_or_default() {
+                let mut code_key: HashMap<String, AttributeValue> = HashMap::new();
+                code_key.insert("product_key".to_string(), string_attr(product_key));
+                code_key.insert("code".to_string(), string_attr(&get_string(&item, "code")));
+
+                let delete_item_input = DeleteItemInput {
+                    key: code_key,
+                    table_name: self.tables.codes.clone(),
+                    ..Default::default()
+                };
+                self.client.delete_item(delete_item_input).await.map_err(|err| err.to_string())?;
+            }
+            match output.last_evaluated_key {
+                Some(key) => start_key = Some(key),
+                None => return Ok(())
+            }
+        }
+    }
+
+    /// Any one of a digital product's unsold codes.
+    async fn next_code(&self, product_key: &str) -> Result<Option<String>, String> {
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":product_key".to_string(), string_attr(product_key));
+
+        let query_input = QueryInput {
+            table_name: self.tables.codes.clone(),
+            key_condition_expression: Some("product_key = :product_key".to_string()),
+            expression_attribute_values: Some(values),
+            limit: Some(1),
+            ..Default::default()
+        };
+
+        match self.client.query(query_input).await {
+            Ok(output) => Ok(output.items.unwrap_or_default().first().map(|item| get_string(item, "code"))),
+            Err(err) => Err(err.to_string())
+        }
+    }
+}
+
+fn string_attr(string: &str) -> AttributeValue {
+    AttributeValue { s: Some(string.to_string()), ..Default::default() }
+}
+
+fn number_attr(number: &i64) -> AttributeValue {
+    AttributeValue { n: Some(number.to_string()), ..Default::default() }
+}
+
+fn bool_attr(boolean: &bool) -> AttributeValue {
+    AttributeValue { bool: Some(*boolean), ..Default::default() }
+}
+
+fn get_number(item: &HashMap<String, AttributeValue>, field: &str) -> i64 {
+    item.get(field).and_then(|attr| attr.n.as_ref()).and_then(|n| n.parse::<i64>().ok()).unwrap_or(0)
+}
+
+fn get_string(item: &HashMap<String, AttributeValue>, field: &str) -> String {
+    item.get(field).and_then(|attr| attr.s.clone()).unwrap_or_default()
+}
+
+fn get_bool(item: &HashMap<String, AttributeValue>, field: &str) -> bool {
+    item.get(field).and_then(|attr| attr.bool).unwrap_or(false)
+}
+
+fn item_to_profile(item: &HashMap<String, AttributeValue>) -> Profile {
+    Profile { points: get_number(item, "points"), credits: get_number(item, "credits") }
+}
+
+fn item_to_product(item: &HashMap<String, AttributeValue>) -> Product {
+    Product {
+        name: get_string(item, "name"),
+        price: get_number(item, "price"),
+        quantity: get_number(item, "quantity"),
+        key: get_string(item, "key"),
+        description: get_string(item, "description"),
+        member_limit: get_number(item, "member_limit"),
+        unlimited: get_bool(item, "unlimited"),
+        digital: get_bool(item, "digital")
+    }
+}
+
+fn item_to_purchase(item: &HashMap<String, AttributeValue>) -> Purchase {
+    Purchase {
+        id: get_string(item, "id"),
+        product_key: get_string(item, "product_key"),
+        discord_id: get_string(item, "discord_id"),
+        price: get_number(item, "price"),
+        timestamp: get_number(item, "timestamp"),
+        status: PurchaseStatus::parse(item.get("status").and_then(|attr| attr.s.as_deref())),
+        code: item.get("code").and_then(|attr| attr.s.clone())
+    }
+}
+
+fn item_to_claim(item: &HashMap<String, AttributeValue>) -> Claim {
+    Claim {
+        id: get_string(item, "id"),
+        activity_id: get_string(item, "activity_id"),
+        discord_id: get_string(item, "discord_id"),
+        evidence: get_string(item, "evidence"),
+        timestamp: get_number(item, "timestamp"),
+        status: ClaimStatus::parse(&get_string(item, "status")),
+        reviewer_id: item.get("reviewer_id").and_then(|attr| attr.s.clone())
+    }
+}
+
+fn item_to_event(item: &HashMap<String, AttributeValue>) -> Event {
+    Event {
+        id: get_string(item, "id"),
+        name: get_string(item, "name"),
+        code: get_string(item, "code"),
+        points: get_number(item, "points"),
+        expires: get_number(item, "expires"),
+        timestamp: get_number(item, "timestamp")
+    }
+}
+
+fn item_to_attendance(item: &HashMap<String, AttributeValue>) -> Attendance {
+    Attendance {
+        event_id: get_string(item, "event_id"),
+        discord_id: get_string(item, "discord_id"),
+        timestamp: get_number(item, "timestamp")
+    }
+}
+
+fn item_to_entry(item: &HashMap<String, AttributeValue>) -> LedgerEntry {
+    LedgerEntry {
+        id: get_string(item, "id"),
+        actor_id: get_string(item, "actor_id"),
+        discord_id: get_string(item, "discord_id"),
+        points: get_number(item, "points"),
+        credits: get_number(item, "credits"),
+        reason: get_string(item, "reason"),
+        timestamp: get_number(item, "timestamp"),
+        message_id: get_string(item, "message_id")
+    }
+}
+
+fn entry_put(table: &str, entry: &LedgerEntry) -> TransactWriteItem {
+    let mut item: HashMap<String, AttributeValue> = HashMap::new();
+    item.insert("id".to_string(), string_attr(&entry.id));
+    item.insert("actor_id".to_string(), string_attr(&entry.actor_id));
+    item.insert("discord_id".to_string(), string_attr(&entry.discord_id));
+    item.insert("points".to_string(), number_attr(&entry.points));
+    item.insert("credits".to_string(), number_attr(&entry.credits));
+    item.insert("reason".to_string(), string_attr(&entry.reason));
+    item.insert("timestamp".to_string(), number_attr(&entry.timestamp));
+    item.insert("message_id".to_string(), string_attr(&entry.message_id));
+
+    TransactWriteItem {
+        put: Some(Put {
+            item,
+            table_name: table.to_string(),
+            condition_expression: Some("attribute_not_exists(id)".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Why each item of a cancelled transaction failed, in the order they were given, e.g.
+/// `["None", "ConditionalCheckFailed"]`. DynamoDB only lists them at the end of the message.
+fn cancellation_reasons(message: &str) -> Vec<String> {
+    message.rsplit_once('[')
+        .and_then(|(_, reasons)| reasons.split(']').next())
+        .map(|reasons| reasons.split(',').map(|reason| reason.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Whether a transaction was cancelled only because conditions on the items at `indices` failed.
+fn conditions_failed(message: &str, indices: &[usize]) -> bool {
+    let reasons = cancellation_reasons(message);
+    reasons.iter().any(|reason| reason == "ConditionalCheckFailed")
+        && reasons.iter().enumerate().all(|(index, reason)| reason == "None" || (reason == "ConditionalCheckFailed" && indices.contains(&index)))
+}
+
+/// Whether a transaction was cancelled because another one was changing the same items.
+fn conflicted(message: &str) -> bool {
+    cancellation_reasons(message).iter().any(|reason| reason == "TransactionConflict")
+}
+
+/// How many of a product a member has bought and not had paid back, kept on their profile
+/// so the member limit can be checked in the same transaction as the purchase. Counting
+/// starts with the first purchase of a limited product, and from then on every purchase,
+/// refund and cancel moves it.
+enum Bought {
+    /// Not counted, and still mustn't be when the transaction runs
+    Uncounted,
+    /// Was `from` when read (`None` if counting starts now) and becomes `to`
+    Counted { from: Option<i64>, to: i64 }
+}
+
+fn bought_attr(product_key: &str) -> String {
+    format!("bought:{}", product_key)
+}
+
+/// The `SET` action and condition that move a profile's count from what was read, so two
+/// purchases made at once can't both go by the same count.
+fn bought_expressions(product_key: &str, bought: &Bought, names: &mut HashMap<String, String>,
+                      values: &mut HashMap<String, AttributeValue>) -> (Option<&'static str>, &'static str) {
+    names.insert("#bought".to_string(), bought_attr(product_key));
+    match bought {
+        Bought::Uncounted => (None, "attribute_not_exists(#bought)"),
+        Bought::Counted { from, to } => {
+            values.insert(":bought".to_string(), number_attr(to));
+            let condition = match from {
+                Some(from) => {
+                    values.insert(":counted".to_string(), number_attr(from));
+                    "#bought = :counted"
+                },
+                None => "attribute_not_exists(#bought)"
+            };
+            (Some("#bought = :bought"), condition)
+        }
+    }
+}
+
+/// Adds an entry's points and credits to its member's profile.
+fn profile_add(table: &str, entry: &LedgerEntry) -> TransactWriteItem {
+    let mut key: HashMap<String, AttributeValue> = HashMap::new();
+    key.insert("discord_id".to_string(), string_attr(&entry.discord_id));
+    let mut values: HashMap<String, AttributeValue> = HashMap::new();
+    values.insert(":points".to_string(), number_attr(&entry.points));
+    values.insert(":credits".to_string(), number_attr(&entry.credits));
+
+    // ADD treats missing attributes as zero, so this also creates new profiles
+    TransactWriteItem {
+        update: Some(Update {
+            key,
+            table_name: table.to_string(),
+            update_expression: "ADD points :points, credits :credits".to_string(),
+            expression_attribute_values: Some(values),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[async_trait]
+impl Storage for DynamoStorage {
+    async fn get_profile(&self, user_id: &str) -> Result<Profile, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("discord_id".to_string(), string_attr(user_id));
+
+        let get_item_input = GetItemInput {
+            key,
+            table_name: self.tables.profiles.clone(),
+            ..Default::default()
+        };
+
+        match self.client.get_item(get_item_input).await {
+            Ok(output) =>
+                match output.item {
+                    Some(item) => Ok(item_to_profile(&item)),
+                    None => Ok(Profile { points: 0, credits: 0 })
+            },
+            Err(err) =>
+                Err(err.to_string())
+        }
+    }
+
+    async fn get_profiles(&self) -> Result<Vec<(String, Profile)>, String> {
+        let mut profiles: Vec<(String, Profile)> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let scan_input = ScanInput {
+                table_name: self.tables.profiles.clone(),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.scan(scan_input).await {
+                Ok(output) => {
+                    profiles.extend(output.items.unwrap_or_default().iter().map(|item| {
+                        (get_string(item, "discord_id"), item_to_profile(item))
+                    }));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(profiles)
+    }
+
+    async fn record_entry(&self, entry: LedgerEntry) -> Result<Profile, String> {
+        let transact_input = TransactWriteItemsInput {
+            transact_items: vec![profile_add(&self.tables.profiles, &entry), entry_put(&self.tables.ledger, &entry)],
+            ..Default::default()
+        };
+
+        match self.client.transact_write_items(transact_input).await {
+            Ok(_) => self.get_profile(&entry.discord_id).await,
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn get_entries(&self, user_id: &str) -> Result<Vec<LedgerEntry>, String> {
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":discord_id".to_string(), string_attr(user_id));
+
+        let mut entries: Vec<LedgerEntry> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let query_input = QueryInput {
+                table_name: self.tables.ledger.clone(),
+                key_condition_expression: Some("discord_id = :discord_id".to_string()),
+                expression_attribute_values: Some(values.clone()),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.query(query_input).await {
+                Ok(output) => {
+                    entries.extend(output.items.unwrap_or_default().iter().map(item_to_entry));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.timestamp);
+        Ok(entries)
+    }
+
+    async fn open_ledger(&self, entry: LedgerEntry) -> Result<bool, String> {
+        if !self.get_entries(&entry.discord_id).await?.is_empty() {
+            return Ok(false);
+        }
+
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("discord_id".to_string(), string_attr(&entry.discord_id));
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":points".to_string(), number_attr(&entry.points));
+        values.insert(":credits".to_string(), number_attr(&entry.credits));
+        // Older profiles can be missing either number, which reads as zero
+        let condition = [("points", entry.points), ("credits", entry.credits)].iter()
+            .map(|(field, amount)| if *amount == 0 {
+                format!("(attribute_not_exists({}) OR {} = :{})", field, field, field)
+            } else {
+                format!("{} = :{}", field, field)
+            })
+            .collect::<Vec<String>>()
+            .join(" AND ");
+
+        // Anything written to the ledger since we looked changed the profile too, so checking the
+        // profile hasn't moved stands in for checking the ledger is still empty
+        let transact_input = TransactWriteItemsInput {
+            transact_items: vec![
+                TransactWriteItem {
+                    condition_check: Some(ConditionCheck {
+                        key,
+                        table_name: self.tables.profiles.clone(),
+                        condition_expression: condition,
+                        expression_attribute_values: Some(values),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                entry_put(&self.tables.ledger, &entry)
+            ],
+            ..Default::default()
+        };
+
+        match self.client.transact_write_items(transact_input).await {
+            Ok(_) => Ok(true),
+            Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conditions_failed(&message, &[0, 1]) => Ok(false),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn transfer(&self, debit: LedgerEntry, credit: LedgerEntry) -> Result<TransferOutcome, String> {
+        let mut sender_key: HashMap<String, AttributeValue> = HashMap::new();
+        sender_key.insert("discord_id".to_string(), string_attr(&debit.discord_id));
+        let mut sender_values: HashMap<String, AttributeValue> = HashMap::new();
+        sender_values.insert(":credits".to_string(), number_attr(&debit.credits));
+        sender_values.insert(":amount".to_string(), number_attr(&-debit.credits));
+
+        let mut recipient_key: HashMap<String, AttributeValue> = HashMap::new();
+        recipient_key.insert("discord_id".to_string(), string_attr(&credit.discord_id));
+        let mut recipient_values: HashMap<String, AttributeValue> = HashMap::new();
+        recipient_values.insert(":credits".to_string(), number_attr(&credit.credits));
+
+        let transact_input = TransactWriteItemsInput {
+            transact_items: vec![
+                TransactWriteItem {
+                    update: Some(Update {
+                        key: sender_key,
+                        table_name: self.tables.profiles.clone(),
+                        update_expression: "ADD credits :credits".to_string(),
+                        condition_expression: Some("credits >= :amount".to_string()),
+                        expression_attribute_values: Some(sender_values),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                TransactWriteItem {
+                    update: Some(Update {
+                        key: recipient_key,
+                        table_name: self.tables.profiles.clone(),
+                        update_expression: "ADD credits :credits".to_string(),
+                        expression_attribute_values: Some(recipient_values),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                entry_put(&self.tables.ledger, &debit),
+                entry_put(&self.tables.ledger, &credit)
+            ],
+            ..Default::default()
+        };
+
+        for _ in 0..TRANSACTION_ATTEMPTS {
+            match self.client.transact_write_items(transact_input.clone()).await {
+                Ok(_) => return Ok(TransferOutcome::Transferred(self.get_profile(&debit.discord_id).await?)),
+                // Only the sender's balance has a condition on it
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conditions_failed(&message, &[0]) => {
+                    let sender = self.get_profile(&debit.discord_id).await?;
+                    return Ok(TransferOutcome::InsufficientCredits(sender.credits));
+                },
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conflicted(&message) => continue,
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Err("Too many changes to these balances at once, please try again".to_string())
+    }
+
+    async fn get_store(&self) -> Result<Vec<Product>, String> {
+        let mut products: Vec<Product> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let scan_input = ScanInput {
+                table_name: self.tables.store.clone(),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.scan(scan_input).await {
+                Ok(output) => {
+                    products.extend(output.items.unwrap_or_default().iter().map(item_to_product));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(products)
+    }
+
+    async fn get_product(&self, product_key: &str) -> Result<Option<Product>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("key".to_string(), string_attr(product_key));
+
+        let get_item_input = GetItemInput {
+            key,
+            table_name: self.tables.store.clone(),
+            ..Default::default()
+        };
+
+        match self.client.get_item(get_item_input).await {
+            Ok(output) => Ok(output.item.map(|item| item_to_product(&item))),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn put_product(&self, product: Product) -> Result<Product, String> {
+        let mut new_item: HashMap<String, AttributeValue> = HashMap::new();
+        new_item.insert("key".to_string(), string_attr(&product.key));
+        new_item.insert("name".to_string(), string_attr(&product.name));
+        new_item.insert("description".to_string(), string_attr(&product.description));
+        new_item.insert("price".to_string(), number_attr(&product.price));
+        new_item.insert("quantity".to_string(), number_attr(&product.quantity));
+        new_item.insert("member_limit".to_string(), number_attr(&product.member_limit));
+        new_item.insert("unlimited".to_string(), bool_attr(&product.unlimited));
+        new_item.insert("digital".to_string(), bool_attr(&product.digital));
+
+        let put_item_input = PutItemInput {
+            item: new_item,
+            table_name: self.tables.store.clone(),
+            ..Default::default()
+        };
+
+        match self.client.put_item(put_item_input).await {
+            Ok(_) => Ok(product),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn delete_product(&self, key: &str) -> Result<String, String> {
+        // There can be more codes than fit in a transaction, so they go first; if that stops
+        // partway, the product is still there to delete again and just has fewer codes to sell
+        self.delete_codes(key).await?;
+
+        let mut delete_key: HashMap<String, AttributeValue> = HashMap::new();
+        delete_key.insert("key".to_string(), string_attr(key));
+
+        let delete_item_input = DeleteItemInput {
+            key: delete_key,
+            table_name: self.tables.store.clone(),
+            ..Default::default()
+        };
+
+        match self.client.delete_item(delete_item_input).await {
+            Ok(_) => Ok(key.to_string()),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn edit_product(&self, key: &str, changes: ProductChanges) -> Result<Option<Product>, String> {
+        let mut names: HashMap<String, String> = HashMap::new();
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        let mut sets: Vec<String> = Vec::new();
+        let fields = vec![
+            ("name", changes.name.as_deref().map(string_attr)),
+            ("description", changes.description.as_deref().map(string_attr)),
+            ("price", changes.price.as_ref().map(number_attr)),
+            ("quantity", changes.quantity.as_ref().map(number_attr)),
+            ("member_limit", changes.member_limit.as_ref().map(number_attr)),
+            ("unlimited", changes.unlimited.as_ref().map(bool_attr))
+        ];
+        for (field, value) in fields {
+            if let Some(value) = value {
+                names.insert(format!("#{}", field), field.to_string());
+                values.insert(format!(":{}", field), value);
+                sets.push(format!("#{} = :{}", field, field));
+            }
+        }
+        if sets.is_empty() {
+            return self.get_product(key).await;
+        }
+
+        let mut product_key: HashMap<String, AttributeValue> = HashMap::new();
+        product_key.insert("key".to_string(), string_attr(key));
+        names.insert("#key".to_string(), "key".to_string());
+        let update_item_input = UpdateItemInput {
+            key: product_key,
+            table_name: self.tables.store.clone(),
+            update_expression: Some(format!("SET {}", sets.join(", "))),
+            condition_expression: Some("attribute_exists(#key)".to_string()),
+            expression_attribute_names: Some(names),
+            expression_attribute_values: Some(values),
+            return_values: Some("ALL_NEW".to_string()),
+            ..Default::default()
+        };
+
+        match self.client.update_item(update_item_input).await {
+            Ok(output) => Ok(output.attributes.map(|item| item_to_product(&item))),
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(None),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn restock(&self, key: &str, amount: i64) -> Result<Option<Product>, String> {
+        let mut product_key: HashMap<String, AttributeValue> = HashMap::new();
+        product_key.insert("key".to_string(), string_attr(key));
+        let mut names: HashMap<String, String> = HashMap::new();
+        names.insert("#key".to_string(), "key".to_string());
+        names.insert("#quantity".to_string(), "quantity".to_string());
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":amount".to_string(), number_attr(&amount));
+
+        let update_item_input = UpdateItemInput {
+            key: product_key,
+            table_name: self.tables.store.clone(),
+            update_expression: Some("ADD #quantity :amount".to_string()),
+            condition_expression: Some("attribute_exists(#key)".to_string()),
+            expression_attribute_names: Some(names),
+            expression_attribute_values: Some(values),
+            return_values: Some("ALL_NEW".to_string()),
+            ..Default::default()
+        };
+
+        match self.client.update_item(update_item_input).await {
+            Ok(output) => Ok(output.attributes.map(|item| item_to_product(&item))),
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(None),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn add_codes(&self, key: &str, codes: &[String]) -> Result<Option<Product>, String> {
+        for code in codes {
+            let mut code_item: HashMap<String, AttributeValue> = HashMap::new();
+            code_item.insert("product_key".to_string(), string_attr(key));
+            code_item.insert("code".to_string(), string_attr(code));
+
+            let mut product_key: HashMap<String, AttributeValue> = HashMap::new();
+            product_key.insert("key".to_string(), string_attr(key));
+            let mut product_names: HashMap<String, String> = HashMap::new();
+            product_names.insert("#key".to_string(), "key".to_string());
+            product_names.insert("#quantity".to_string(), "quantity".to_string());
+            product_names.insert("#digital".to_string(), "digital".to_string());
+            product_names.insert("#unlimited".to_string(), "unlimited".to_string());
+            let mut product_values: HashMap<String, AttributeValue> = HashMap::new();
+            product_values.insert(":one".to_string(), number_attr(&1));
+            product_values.insert(":true".to_string(), bool_attr(&true));
+            product_values.insert(":false".to_string(), bool_attr(&false));
+
+            // One code at a time, so a code that's already in the pool only skips itself
+            let transact_input = TransactWriteItemsInput {
+                transact_items: vec![
+                    TransactWriteItem {
+                        put: Some(Put {
+                            item: code_item,
+                            table_name: self.tables.codes.clone(),
+                            condition_expression: Some("attribute_not_exists(code)".to_string()),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                    TransactWriteItem {
+                        update: Some(Update {
+                            key: product_key,
+                            table_name: self.tables.store.clone(),
+                            update_expression: "SET #digital = :true, #unlimited = :false ADD #quantity :one".to_string(),
+                            condition_expression: Some("attribute_exists(#key)".to_string()),
+                            expression_attribute_names: Some(product_names),
+                            expression_attribute_values: Some(product_values),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }
+                ],
+                ..Default::default()
+            };
+
+            match self.client.transact_write_items(transact_input).await {
+                Ok(_) => {},
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(_))) => {
+                    if self.get_product(key).await?.is_none() {
+                        return Ok(None);
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        self.get_product(key).await
+    }
+
+    async fn buy(&self, purchase: Purchase, entry: LedgerEntry) -> Result<PurchaseOutcome, String> {
+        for _ in 0..TRANSACTION_ATTEMPTS {
+            let product = match self.get_product(&purchase.product_key).await? {
+                Some(product) => product,
+                None => return Ok(PurchaseOutcome::NoSuchProduct)
+            };
+            let profile = self.get_profile(&purchase.discord_id).await?;
+            let bought = match self.get_bought(&purchase.discord_id, &product.key).await? {
+                Some(count) => Bought::Counted { from: Some(count), to: count + 1 },
+                None if product.member_limit > 0 => {
+                    let count = count_bought(&self.get_purchases_by_member(&purchase.discord_id).await?, &product.key);
+                    Bought::Counted { from: None, to: count + 1 }
+                },
+                None => Bought::Uncounted
+            };
+            let already = match bought {
+                Bought::Counted { to, .. } => to - 1,
+                Bought::Uncounted => 0
+            };
+            if let Some(refusal) = refuse_purchase(&profile, &product, already) {
+                return Ok(refusal);
+            }
+            let code = if product.digital {
+                match self.next_code(&product.key).await? {
+                    Some(code) => Some(code),
+                    None => return Ok(PurchaseOutcome::OutOfStock(product))
+                }
+            } else {
+                None
+            };
+
+            // The conditions re-check what we just read, so if anything changed in
+            // between the whole transaction is cancelled and we go around again
+            let entry = LedgerEntry { points: 0, credits: -product.price, ..entry.clone() };
+            let transaction = purchase_transaction(&self.tables, &purchase, &product, code.as_deref(), &bought, &entry);
+            match self.client.transact_write_items(transaction).await {
+                Ok(_) => {
+                    let profile = self.get_profile(&purchase.discord_id).await?;
+                    let quantity = if product.unlimited { product.quantity } else { product.quantity - 1 };
+                    return Ok(PurchaseOutcome::Purchased { product: Product { quantity, ..product }, profile, code });
+                },
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(_))) => continue,
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Err("The store is busy, please try again".to_string())
+    }
+
+    async fn refund(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        self.pay_back(id, PurchaseStatus::Refunded, entry).await
+    }
+
+    async fn cancel(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        self.pay_back(id, PurchaseStatus::Cancelled, entry).await
+    }
+
+    async fn get_purchase(&self, id: &str) -> Result<Option<Purchase>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("id".to_string(), string_attr(id));
+
+        let get_item_input = GetItemInput {
+            key,
+            table_name: self.tables.purchases.clone(),
+            ..Default::default()
+        };
+
+        match self.client.get_item(get_item_input).await {
+            Ok(output) => Ok(output.item.map(|item| item_to_purchase(&item))),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn get_purchases_by_member(&self, user_id: &str) -> Result<Vec<Purchase>, String> {
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":discord_id".to_string(), string_attr(user_id));
+
+        let mut purchases: Vec<Purchase> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let scan_input = ScanInput {
+                table_name: self.tables.purchases.clone(),
+                filter_expression: Some("discord_id = :discord_id".to_string()),
+                expression_attribute_values: Some(values.clone()),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.scan(scan_input).await {
+                Ok(output) => {
+                    purchases.extend(output.items.unwrap_or_default().iter().map(item_to_purchase));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(purchases)
+    }
+
+    async fn get_purchases_by_status(&self, status: PurchaseStatus) -> Result<Vec<Purchase>, String> {
+        // Older purchases have no status at all, so filter here rather than in the scan
+        let mut purchases: Vec<Purchase> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let scan_input = ScanInput {
+                table_name: self.tables.purchases.clone(),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.scan(scan_input).await {
+                Ok(output) => {
+                    purchases.extend(output.items.unwrap_or_default().iter()
+                        .map(item_to_purchase)
+                        .filter(|purchase| purchase.status == status));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(purchases)
+    }
+
+    async fn update_purchase_status(&self, id: &str, from: PurchaseStatus, to: PurchaseStatus) -> Result<Option<Purchase>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("id".to_string(), string_attr(id));
+        let mut names: HashMap<String, String> = HashMap::new();
+        names.insert("#status".to_string(), "status".to_string());
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":from".to_string(), string_attr(from.as_str()));
+        values.insert(":to".to_string(), string_attr(to.as_str()));
+
+        let condition = if from == PurchaseStatus::Pending {
+            "attribute_exists(id) AND (#status = :from OR attribute_not_exists(#status))"
+        } else {
+            "attribute_exists(id) AND #status = :from"
+        };
+        let update_item_input = UpdateItemInput {
+            key,
+            table_name: self.tables.purchases.clone(),
+            update_expression: Some("SET #status = :to".to_string()),
+            condition_expression: Some(condition.to_string()),
+            expression_attribute_names: Some(names),
+            expression_attribute_values: Some(values),
+            return_values: Some("ALL_NEW".to_string()),
+            ..Default::default()
+        };
+
+        match self.client.update_item(update_item_input).await {
+            Ok(output) => Ok(output.attributes.map(|item| item_to_purchase(&item))),
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(None),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn add_event(&self, event: Event) -> Result<EventOutcome, String> {
+        let mut new_item: HashMap<String, AttributeValue> = HashMap::new();
+        new_item.insert("id".to_string(), string_attr(&event.id));
+        new_item.insert("name".to_string(), string_attr(&event.name));
+        new_item.insert("code".to_string(), string_attr(&event.code));
+        new_item.insert("points".to_string(), number_attr(&event.points));
+        new_item.insert("expires".to_string(), number_attr(&event.expires));
+        new_item.insert("timestamp".to_string(), number_attr(&event.timestamp));
+
+        // The code's item says which event has it until when, so taking it can be conditional
+        let mut code_item: HashMap<String, AttributeValue> = HashMap::new();
+        code_item.insert("code".to_string(), string_attr(&event.code));
+        code_item.insert("event_id".to_string(), string_attr(&event.id));
+        code_item.insert("expires".to_string(), number_attr(&event.expires));
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":now".to_string(), number_attr(&event.timestamp));
+
+        let transact_input = TransactWriteItemsInput {
+            transact_items: vec![
+                TransactWriteItem {
+                    put: Some(Put {
+                        item: new_item,
+                        table_name: self.tables.events.clone(),
+                        condition_expression: Some("attribute_not_exists(id)".to_string()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                TransactWriteItem {
+                    put: Some(Put {
+                        item: code_item,
+                        table_name: self.tables.event_codes.clone(),
+                        condition_expression: Some("attribute_not_exists(code) OR expires <= :now".to_string()),
+                        expression_attribute_values: Some(values),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }
+            ],
+            ..Default::default()
+        };
+
+        for _ in 0..TRANSACTION_ATTEMPTS {
+            match self.client.transact_write_items(transact_input.clone()).await {
+                Ok(_) => return Ok(EventOutcome::Added),
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conditions_failed(&message, &[0]) => return Ok(EventOutcome::IdTaken),
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conditions_failed(&message, &[0, 1]) => {
+                    match self.get_code_event(&event.code).await? {
+                        Some(clash) => return Ok(EventOutcome::CodeInUse(clash)),
+                        // Its event went in the meantime, so try for the code again
+                        None => continue
+                    }
+                },
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conflicted(&message) => continue,
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Err("Too many events being added at once, please try again".to_string())
+    }
+
+    async fn get_event(&self, id: &str) -> Result<Option<Event>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("id".to_string(), string_attr(id));
+
+        let get_item_input = GetItemInput {
+            key,
+            table_name: self.tables.events.clone(),
+            ..Default::default()
+        };
+
+        match self.client.get_item(get_item_input).await {
+            Ok(output) => Ok(output.item.map(|item| item_to_event(&item))),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>, String> {
+        let mut events: Vec<Event> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let scan_input = ScanInput {
+                table_name: self.tables.events.clone(),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.scan(scan_input).await {
+                Ok(output) => {
+                    events.extend(output.items.unwrap_or_default().iter().map(item_to_event));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(events)
+    }
+
+    async fn check_in(&self, attendance: Attendance, entry: LedgerEntry) -> Result<Option<Profile>, String> {
+        let mut item: HashMap<String, AttributeValue> = HashMap::new();
+        item.insert("event_id".to_string(), string_attr(&attendance.event_id));
+        item.insert("discord_id".to_string(), string_attr(&attendance.discord_id));
+        item.insert("timestamp".to_string(), number_attr(&attendance.timestamp));
+
+        let attendance_put = TransactWriteItem {
+            put: Some(Put {
+                item,
+                table_name: self.tables.attendance.clone(),
+                condition_expression: Some("attribute_not_exists(discord_id)".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let transact_input = TransactWriteItemsInput {
+            transact_items: vec![attendance_put, profile_add(&self.tables.profiles, &entry), entry_put(&self.tables.ledger, &entry)],
+            ..Default::default()
+        };
+
+        for _ in 0..TRANSACTION_ATTEMPTS {
+            match self.client.transact_write_items(transact_input.clone()).await {
+                Ok(_) => return Ok(Some(self.get_profile(&entry.discord_id).await?)),
+                // Only the attendance has a condition on it, so they were already there
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conditions_failed(&message, &[0]) => return Ok(None),
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conflicted(&message) => continue,
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Err("Too many changes to this balance at once, please try again".to_string())
+    }
+
+    async fn get_attendance(&self, event_id: &str) -> Result<Vec<Attendance>, String> {
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":event_id".to_string(), string_attr(event_id));
+
+        let mut attendance: Vec<Attendance> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let query_input = QueryInput {
+                table_name: self.tables.attendance.clone(),
+                key_condition_expression: Some("event_id = :event_id".to_string()),
+                expression_attribute_values: Some(values.clone()),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.query(query_input).await {
+                Ok(output) => {
+                    attendance.extend(output.items.unwrap_or_default().iter().map(item_to_attendance));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(attendance)
+    }
+
+    async fn put_claim(&self, claim: Claim) -> Result<Claim, String> {
+        let mut new_item: HashMap<String, AttributeValue> = HashMap::new();
+        new_item.insert("id".to_string(), string_attr(&claim.id));
+        new_item.insert("activity_id".to_string(), string_attr(&claim.activity_id));
+        new_item.insert("discord_id".to_string(), string_attr(&claim.discord_id));
+        new_item.insert("evidence".to_string(), string_attr(&claim.evidence));
+        new_item.insert("timestamp".to_string(), number_attr(&claim.timestamp));
+        new_item.insert("status".to_string(), string_attr(claim.status.as_str()));
+        if let Some(reviewer_id) = &claim.reviewer_id {
+            new_item.insert("reviewer_id".to_string(), string_attr(reviewer_id));
+        }
+
+        let put_item_input = PutItemInput {
+            item: new_item,
+            table_name: self.tables.claims.clone(),
+            ..Default::default()
+        };
+
+        match self.client.put_item(put_item_input).await {
+            Ok(_) => Ok(claim),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn get_claim(&self, id: &str) -> Result<Option<Claim>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("id".to_string(), string_attr(id));
+
+        let get_item_input = GetItemInput {
+            key,
+            table_name: self.tables.claims.clone(),
+            ..Default::default()
+        };
+
+        match self.client.get_item(get_item_input).await {
+            Ok(output) => Ok(output.item.map(|item| item_to_claim(&item))),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn get_claims_by_member(&self, user_id: &str) -> Result<Vec<Claim>, String> {
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":discord_id".to_string(), string_attr(user_id));
+
+        let mut claims: Vec<Claim> = Vec::new();
+        let mut start_key = None;
+        loop {
+            let scan_input = ScanInput {
+                table_name: self.tables.claims.clone(),
+                filter_expression: Some("discord_id = :discord_id".to_string()),
+                expression_attribute_values: Some(values.clone()),
+                exclusive_start_key: start_key,
+                ..Default::default()
+            };
+
+            match self.client.scan(scan_input).await {
+                Ok(output) => {
+                    claims.extend(output.items.unwrap_or_default().iter().map(item_to_claim));
+                    match output.last_evaluated_key {
+                        Some(key) => start_key = Some(key),
+                        None => break
+                    }
+                },
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Ok(claims)
+    }
+
+    async fn review_claim(&self, id: &str, status: ClaimStatus, reviewer_id: &str) -> Result<Option<Claim>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("id".to_string(), string_attr(id));
+        let mut names: HashMap<String, String> = HashMap::new();
+        names.insert("#status".to_string(), "status".to_string());
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":pending".to_string(), string_attr(ClaimStatus::Pending.as_str()));
+        values.insert(":status".to_string(), string_attr(status.as_str()));
+        values.insert(":reviewer_id".to_string(), string_attr(reviewer_id));
+
+        let update_item_input = UpdateItemInput {
+            key,
+            table_name: self.tables.claims.clone(),
+            update_expression: Some("SET #status = :status, reviewer_id = :reviewer_id".to_string()),
+            condition_expression: Some("attribute_exists(id) AND #status = :pending".to_string()),
+            expression_attribute_names: Some(names),
+            expression_attribute_values: Some(values),
+            return_values: Some("ALL_NEW".to_string()),
+            ..Default::default()
+        };
+
+        match self.client.update_item(update_item_input).await {
+            Ok(output) => Ok(output.attributes.map(|item| item_to_claim(&item))),
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(None),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    async fn approve_claim(&self, id: &str, reviewer_id: &str, entry: LedgerEntry) -> Result<Option<(Claim, Profile)>, String> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+        key.insert("id".to_string(), string_attr(id));
+        let mut names: HashMap<String, String> = HashMap::new();
+        names.insert("#status".to_string(), "status".to_string());
+        let mut values: HashMap<String, AttributeValue> = HashMap::new();
+        values.insert(":pending".to_string(), string_attr(ClaimStatus::Pending.as_str()));
+        values.insert(":status".to_string(), string_attr(ClaimStatus::Approved.as_str()));
+        values.insert(":reviewer_id".to_string(), string_attr(reviewer_id));
+
+        let claim_update = TransactWriteItem {
+            update: Some(Update {
+                key,
+                table_name: self.tables.claims.clone(),
+                update_expression: "SET #status = :status, reviewer_id = :reviewer_id".to_string(),
+                condition_expression: Some("attribute_exists(id) AND #status = :pending".to_string()),
+                expression_attribute_names: Some(names),
+                expression_attribute_values: Some(values),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let transact_input = TransactWriteItemsInput {
+            transact_items: vec![claim_update, profile_add(&self.tables.profiles, &entry), entry_put(&self.tables.ledger, &entry)],
+            ..Default::default()
+        };
+
+        for _ in 0..TRANSACTION_ATTEMPTS {
+            match self.client.transact_write_items(transact_input.clone()).await {
+                Ok(_) => {
+                    let claim = self.get_claim(id).await?.ok_or_else(|| format!("Claim {} vanished after approval", id))?;
+                    return Ok(Some((claim, self.get_profile(&entry.discord_id).await?)));
+                },
+                // Only the claim has a condition on it, so it's gone or was already reviewed
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conditions_failed(&message, &[0]) => return Ok(None),
+                Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(message))) if conflicted(&message) => continue,
+                Err(err) => return Err(err.to_string())
+            }
+        }
+        Err("Too many changes to this balance at once, please try again".to_string())
+    }
+}
+
+/// Buys `product`, handing over `code` from its pool if it's digital.
+fn purchase_transaction(tables: &TablesConfig, purchase: &Purchase, product: &Product, code: Option<&str>, bought: &Bought, entry: &LedgerEntry) -> TransactWriteItemsInput {
+    let mut purchase_item: HashMap<String, AttributeValue> = HashMap::new();
+    purchase_item.insert("id".to_string(), string_attr(&purchase.id));
+    purchase_item.insert("product_key".to_string(), string_attr(&purchase.product_key));
+    purchase_item.insert("discord_id".to_string(), string_attr(&purchase.discord_id));
+    purchase_item.insert("price".to_string(), number_attr(&product.price));
+    purchase_item.insert("timestamp".to_string(), number_attr(&purchase.timestamp));
+    purchase_item.insert("status".to_string(), string_attr(purchase.status.as_str()));
+    if let Some(code) = code {
+        purchase_item.insert("code".to_string(), string_attr(code));
+    }
+
+    let mut product_key: HashMap<String, AttributeValue> = HashMap::new();
+    product_key.insert("key".to_string(), string_attr(&product.key));
+    let mut product_names: HashMap<String, String> = HashMap::new();
+    product_names.insert("#price".to_string(), "price".to_string());
+    let mut product_values: HashMap<String, AttributeValue> = HashMap::new();
+    product_values.insert(":price".to_string(), number_attr(&product.price));
+
+    // Unlimited products only need checking that they haven't changed, not taking out of stock
+    let product_item = if product.unlimited {
+        product_names.insert("#unlimited".to_string(), "unlimited".to_string());
+        product_values.insert(":unlimited".to_string(), bool_attr(&true));
+        TransactWriteItem {
+            condition_check: Some(ConditionCheck {
+                key: product_key,
+                table_name: tables.store.clone(),
+                condition_expression: "#unlimited = :unlimited AND #price = :price".to_string(),
+                expression_attribute_names: Some(product_names),
+                expression_attribute_values: Some(product_values),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    } else {
+        product_names.insert("#quantity".to_string(), "quantity".to_string());
+        product_values.insert(":one".to_string(), number_attr(&1));
+        TransactWriteItem {
+            update: Some(Update {
+                key: product_key,
+                table_name: tables.store.clone(),
+                update_expression: "SET #quantity = #quantity - :one".to_string(),
+                condition_expression: Some("#quantity >= :one AND #price = :price".to_string()),
+                expression_attribute_names: Some(product_names),
+                expression_attribute_values: Some(product_values),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    };
+
+    let mut profile_key: HashMap<String, AttributeValue> = HashMap::new();
+    profile_key.insert("discord_id".to_string(), string_attr(&purchase.discord_id));
+    let mut profile_names: HashMap<String, String> = HashMap::new();
+    profile_names.insert("#credits".to_string(), "credits".to_string());
+    let mut profile_values: HashMap<String, AttributeValue> = HashMap::new();
+    profile_values.insert(":zero".to_string(), number_attr(&0));
+    profile_values.insert(":price".to_string(), number_attr(&product.price));
+
+    // Free products can be bought by members without a profile yet
+    let (bought_set, bought_condition) = bought_expressions(&product.key, bought, &mut profile_names, &mut profile_values);
+    let mut profile_update = "SET #credits = if_not_exists(#credits, :zero) - :price".to_string();
+    if let Some(bought_set) = bought_set {
+        profile_update.push_str(&format!(", {}", bought_set));
+    }
+    let profile_condition = if product.price > 0 {
+        format!("#credits >= :price AND {}", bought_condition)
+    } else {
+        bought_condition.to_string()
+    };
+
+    let mut transact_items = vec![
+        TransactWriteItem {
+            put: Some(Put {
+                item: purchase_item,
+                table_name: tables.purchases.clone(),
+                condition_expression: Some("attribute_not_exists(id)".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        product_item,
+        TransactWriteItem {
+            update: Some(Update {
+                key: profile_key,
+                table_name: tables.profiles.clone(),
+                update_expression: profile_update,
+                condition_expression: Some(profile_condition),
+                expression_attribute_names: Some(profile_names),
+                expression_attribute_values: Some(profile_values),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        entry_put(&tables.ledger, entry)
+    ];
+
+    // Taking the code out of the pool fails the transaction if someone else just got it
+    if let Some(code) = code {
+        let mut code_key: HashMap<String, AttributeValue> = HashMap::new();
+        code_key.insert("product_key".to_string(), string_attr(&product.key));
+        code_key.insert("code".to_string(), string_attr(code));
+        transact_items.push(TransactWriteItem {
+            delete: Some(Delete {
+                key: code_key,
+                table_name: tables.codes.clone(),
+                condition_expression: Some("attribute_exists(code)".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+    }
+
+    TransactWriteItemsInput {
+        transact_items,
+        ..Default::default()
+    }
+}
+
+/// Moves `purchase` to `to`, refunding or cancelling it, and only puts the product back in
+/// stock if `restock`. Nothing changes unless its status and the member's count of what
+/// they've bought are still as they were read.
+fn refund_transaction(tables: &TablesConfig, purchase: &Purchase, to: PurchaseStatus, restock: bool, bought: &Bought, entry: &LedgerEntry) -> TransactWriteItemsInput {
+    let mut purchase_key: HashMap<String, AttributeValue> = HashMap::new();
+    purchase_key.insert("id".to_string(), string_attr(&purchase.id));
+    let mut purchase_names: HashMap<String, String> = HashMap::new();
+    purchase_names.insert("#status".to_string(), "status".to_string());
+    let mut purchase_values: HashMap<String, AttributeValue> = HashMap::new();
+    purchase_values.insert(":from".to_string(), string_attr(purchase.status.as_str()));
+    purchase_values.insert(":to".to_string(), string_attr(to.as_str()));
+    // Purchases from before statuses existed have none and count as pending
+    let purchase_condition = if purchase.status == PurchaseStatus::Pending {
+        "attribute_exists(id) AND (attribute_not_exists(#status) OR #status = :from)"
+    } else {
+        "attribute_exists(id) AND #status = :from"
+    };
+
+    let mut profile_key: HashMap<String, AttributeValue> = HashMap::new();
+    profile_key.insert("discord_id".to_string(), string_attr(&purchase.discord_id));
+    let mut profile_names: HashMap<String, String> = HashMap::new();
+    let mut profile_values: HashMap<String, AttributeValue> = HashMap::new();
+    profile_values.insert(":credits".to_string(), number_attr(&purchase.price));
+    let (bought_set, bought_condition) = bought_expressions(&purchase.product_key, bought, &mut profile_names, &mut profile_values);
+    let profile_update = match bought_set {
+        Some(bought_set) => format!("SET {} ADD credits :credits", bought_set),
+        None => "ADD credits :credits".to_string()
+    };
+
+    let mut transact_items = vec![
+        TransactWriteItem {
+            update: Some(Update {
+                key: purchase_key,
+                table_name: tables.purchases.clone(),
+                update_expression: "SET #status = :to".to_string(),
+                condition_expression: Some(purchase_condition.to_string()),
+                expression_attribute_names: Some(purchase_names),
+                expression_attribute_values: Some(purchase_values),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        TransactWriteItem {
+            update: Some(Update {
+                key: profile_key,
+                table_name: tables.profiles.clone(),
+                update_expression: profile_update,
+                condition_expression: Some(bought_condition.to_string()),
+                expression_attribute_names: Some(profile_names),
+                expression_attribute_values: Some(profile_values),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        entry_put(&tables.ledger, entry)
+    ];
+
+    if restock {
+        let mut product_key: HashMap<String, AttributeValue> = HashMap::new();
+        product_key.insert("key".to_string(), string_attr(&purchase.product_key));
+        let mut product_names: HashMap<String, String> = HashMap::new();
+        product_names.insert("#quantity".to_string(), "quantity".to_string());
+        let mut product_values: HashMap<String, AttributeValue> = HashMap::new();
+        product_values.insert(":one".to_string(), number_attr(&1));
+
+        // Without the condition, a product deleted in the meantime would come back with only a quantity
+        transact_items.push(TransactWriteItem {
+            update: Some(Update {
+                key: product_key,
+                table_name: tables.store.clone(),
+                update_expression: "ADD #quantity :one".to_string(),
+                condition_expression: Some("attribute_exists(#quantity)".to_string()),
+                expression_attribute_names: Some(product_names),
+                expression_attribute_values: Some(product_values),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+    }
+
+    TransactWriteItemsInput {
+        transact_items,
+        ..Default::default()
+    }
+}
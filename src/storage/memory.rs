@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serenity::async_trait;
+
+use super::{Storage, Attendance, Claim, ClaimStatus, Event, EventOutcome, Profile, Product, ProductChanges, Purchase, PurchaseOutcome, PurchaseStatus, RefundOutcome, TransferOutcome, LedgerEntry, can_pay_back, count_bought, refuse_purchase};
+
+/// Keeps everything in process. Nothing survives a restart, which makes it
+/// handy for developing commands without an AWS account.
+#[derive(Default)]
+pub struct MemoryStorage {
+    profiles: Mutex<HashMap<String, Profile>>,
+    products: Mutex<HashMap<String, Product>>,
+    /// Unsold codes for each digital product
+    codes: Mutex<HashMap<String, Vec<String>>>,
+    purchases: Mutex<Vec<Purchase>>,
+    claims: Mutex<Vec<Claim>>,
+    events: Mutex<HashMap<String, Event>>,
+    attendance: Mutex<Vec<Attendance>>,
+    ledger: Mutex<Vec<LedgerEntry>>
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        Default::default()
+    }
+
+    /// Refunds or cancels a purchase, depending on `to`.
+    fn pay_back(&self, id: &str, to: PurchaseStatus, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        let mut products = self.products.lock().map_err(|err| err.to_string())?;
+        let mut profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        let mut purchases = self.purchases.lock().map_err(|err| err.to_string())?;
+        let mut ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+
+        let purchase = match purchases.iter_mut().find(|purchase| purchase.id == id) {
+            Some(purchase) => purchase,
+            None => return Ok(RefundOutcome::NoSuchPurchase)
+        };
+        if !can_pay_back(purchase.status, to) {
+            return Ok(RefundOutcome::AlreadyClosed(purchase.clone()));
+        }
+        if purchase.price_unknown() {
+            return Ok(RefundOutcome::PriceUnknown(purchase.clone()));
+        }
+
+        purchase.status = to;
+        if let Some(product) = products.get_mut(&purchase.product_key).filter(|product| !product.digital) {
+            product.quantity += 1;
+        }
+        let profile = profiles.entry(purchase.discord_id.clone()).or_default();
+        profile.credits += purchase.price;
+        ledger.push(LedgerEntry { discord_id: purchase.discord_id.clone(), points: 0, credits: purchase.price, ..entry });
+        Ok(RefundOutcome::Refunded { purchase: purchase.clone(), profile: profile.clone() })
+    }
+
+    /// Records a purchase as it is, like those from before prices were recorded.
+    #[cfg(test)]
+    pub fn put_legacy_purchase(&self, purchase: Purchase) {
+        self.purchases.lock().unwrap().push(purchase);
+    }
+
+    /// Gives a member a balance with nothing in the ledger behind it, like those from before the ledger.
+    #[cfg(test)]
+    pub fn put_legacy_profile(&self, user_id: &str, profile: Profile) {
+        self.profiles.lock().unwrap().insert(user_id.to_string(), profile);
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn get_profile(&self, user_id: &str) -> Result<Profile, String> {
+        let profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        Ok(profiles.get(user_id).cloned().unwrap_or_default())
+    }
+
+    async fn get_profiles(&self) -> Result<Vec<(String, Profile)>, String> {
+        let profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        Ok(profiles.iter().map(|(user_id, profile)| (user_id.clone(), profile.clone())).collect())
+    }
+
+    async fn record_entry(&self, entry: LedgerEntry) -> Result<Profile, String> {
+        let mut profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        let mut ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+        let profile = profiles.entry(entry.discord_id.clone()).or_default();
+        profile.points += entry.points;
+        profile.credits += entry.credits;
+        ledger.push(entry);
+        Ok(profile.clone())
+    }
+
+    async fn get_entries(&self, user_id: &str) -> Result<Vec<LedgerEntry>, String> {
+        let ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+        Ok(ledger.iter().filter(|entry| entry.discord_id == user_id).cloned().collect())
+    }
+
+    async fn open_ledger(&self, entry: LedgerEntry) -> Result<bool, String> {
+        let profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        let mut ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+        let profile = profiles.get(&entry.discord_id).cloned().unwrap_or_default();
+        if ledger.iter().any(|existing| existing.discord_id == entry.discord_id) || (profile.points, profile.credits) != (entry.points, entry.credits) {
+            return Ok(false);
+        }
+        ledger.push(entry);
+        Ok(true)
+    }
+
+    async fn transfer(&self, debit: LedgerEntry, credit: LedgerEntry) -> Result<TransferOutcome, String> {
+        let mut profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        let mut ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+
+        let sender = profiles.get(&debit.discord_id).cloned().unwrap_or_default();
+        if sender.credits + debit.credits < 0 {
+            return Ok(TransferOutcome::InsufficientCredits(sender.credits));
+        }
+
+        profiles.entry(credit.discord_id.clone()).or_default().credits += credit.credits;
+        let sender = profiles.entry(debit.discord_id.clone()).or_default();
+        sender.credits += debit.credits;
+        let sender = sender.clone();
+        ledger.push(debit);
+        ledger.push(credit);
+        Ok(TransferOutcome::Transferred(sender))
+    }
+
+    async fn get_store(&self) -> Result<Vec<Product>, String> {
+        let products = self.products.lock().map_err(|err| err.to_string())?;
+        Ok(products.values().cloned().collect())
+    }
+
+    async fn get_product(&self, product_key: &str) -> Result<Option<Product>, String> {
+        let products = self.products.lock().map_err(|err| err.to_string())?;
+        Ok(products.get(product_key).cloned())
+    }
+
+    async fn put_product(&self, product: Product) -> Result<Product, String> {
+        let mut products = self.products.lock().map_err(|err| err.to_string())?;
+        products.insert(product.key.clone(), product.clone());
+        Ok(product)
+    }
+
+    async fn delete_product(&self, key: &str) -> Result<String, String> {
+        let mut products = self.products.lock().map_err(|err| err.to_string())?;
+        let mut pools = self.codes.lock().map_err(|err| err.to_string())?;
+        products.remove(key);
+        pools.remove(key);
+        Ok(key.to_string())
+    }
+
+    async fn edit_product(&self, key: &str, changes: ProductChanges) -> Result<Option<Product>, String> {
+        let mut products = self.products.lock().map_err(|err| err.to_string())?;
+        Ok(products.get_mut(key).map(|product| {
+            changes.apply(product);
+            product.clone()
+        }))
+    }
+
+    async fn restock(&self, key: &str, amount: i64) -> Result<Option<Product>, String> {
+        let mut products = self.products.lock().map_err(|err| err.to_string())?;
+        Ok(products.get_mut(key).map(|product| {
+            product.quantity += amount;
+            product.clone()
+        }))
+    }
+
+    async fn add_codes(&self, key: &str, codes: &[String]) -> Result<Option<Product>, String> {
+        let mut products = self.products.lock().map_err(|err| err.to_string())?;
+        let mut pools = self.codes.lock().map_err(|err| err.to_string())?;
+        let product = match products.get_mut(key) {
+            Some(product) => product,
+            None => return Ok(None)
+        };
+        let pool = pools.entry(key.to_string()).or_default();
+        for code in codes {
+            if !pool.contains(code) {
+                pool.push(code.clone());
+                product.quantity += 1;
+            }
+        }
+        product.digital = true;
+        product.unlimited = false;
+        Ok(Some(product.clone()))
+    }
+
+    async fn buy(&self, purchase: Purchase, entry: LedgerEntry) -> Result<PurchaseOutcome, String> {
+        // Hold every lock for the whole purchase so nothing can change underneath us
+        let mut products = self.products.lock().map_err(|err| err.to_string())?;
+        let mut pools = self.codes.lock().map_err(|err| err.to_string())?;
+        let mut profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        let mut purchases = self.purchases.lock().map_err(|err| err.to_string())?;
+        let mut ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+
+        let product = match products.get_mut(&purchase.product_key) {
+            Some(product) => product,
+            None => return Ok(PurchaseOutcome::NoSuchProduct)
+        };
+        let profile = profiles.entry(purchase.discord_id.clone()).or_default();
+        let bought: Vec<Purchase> = purchases.iter().filter(|bought| bought.discord_id == purchase.discord_id).cloned().collect();
+        if let Some(refusal) = refuse_purchase(profile, product, count_bought(&bought, &product.key)) {
+            return Ok(refusal);
+        }
+        let code = match (product.digital, pools.get_mut(&product.key)) {
+            (false, _) => None,
+            (true, Some(pool)) if !pool.is_empty() => Some(pool.remove(0)),
+            (true, _) => return Ok(PurchaseOutcome::OutOfStock(product.clone()))
+        };
+
+        if !product.unlimited {
+            product.quantity -= 1;
+        }
+        profile.credits -= product.price;
+        purchases.push(Purchase { price: product.price, code: code.clone(), ..purchase });
+        ledger.push(LedgerEntry { points: 0, credits: -product.price, ..entry });
+        Ok(PurchaseOutcome::Purchased { product: product.clone(), profile: profile.clone(), code })
+    }
+
+    async fn refund(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        self.pay_back(id, PurchaseStatus::Refunded, entry)
+    }
+
+    async fn cancel(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        self.pay_back(id, PurchaseStatus::Cancelled, entry)
+    }
+
+    async fn get_purchase(&self, id: &str) -> Result<Option<Purchase>, String> {
+        let purchases = self.purchases.lock().map_err(|err| err.to_string())?;
+        Ok(purchases.iter().find(|purchase| purchase.id == id).cloned())
+    }
+
+    async fn get_purchases_by_member(&self, user_id: &str) -> Result<Vec<Purchase>, String> {
+        let purchases = self.purchases.lock().map_err(|err| err.to_string())?;
+        Ok(purchases.iter().filter(|purchase| purchase.discord_id == user_id).cloned().collect())
+    }
+
+    async fn get_purchases_by_status(&self, status: PurchaseStatus) -> Result<Vec<Purchase>, String> {
+        let purchases = self.purchases.lock().map_err(|err| err.to_string())?;
+        Ok(purchases.iter().filter(|purchase| purchase.status == status).cloned().collect())
+    }
+
+    async fn update_purchase_status(&self, id: &str, from: PurchaseStatus, to: PurchaseStatus) -> Result<Option<Purchase>, String> {
+        let mut purchases = self.purchases.lock().map_err(|err| err.to_string())?;
+        match purchases.iter_mut().find(|purchase| purchase.id == id && purchase.status == from) {
+            Some(purchase) => {
+                purchase.status = to;
+                Ok(Some(purchase.clone()))
+            },
+            None => Ok(None)
+        }
+    }
+
+    async fn add_event(&self, event: Event) -> Result<EventOutcome, String> {
+        let mut events = self.events.lock().map_err(|err| err.to_string())?;
+        if let Some(clash) = events.values().find(|existing| existing.code == event.code && existing.expires > event.timestamp) {
+            return Ok(EventOutcome::CodeInUse(clash.clone()));
+        }
+        if events.contains_key(&event.id) {
+            return Ok(EventOutcome::IdTaken);
+        }
+        events.insert(event.id.clone(), event);
+        Ok(EventOutcome::Added)
+    }
+
+    async fn get_event(&self, id: &str) -> Result<Option<Event>, String> {
+        let events = self.events.lock().map_err(|err| err.to_string())?;
+        Ok(events.get(id).cloned())
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>, String> {
+        let events = self.events.lock().map_err(|err| err.to_string())?;
+        Ok(events.values().cloned().collect())
+    }
+
+    async fn check_in(&self, attendance: Attendance, entry: LedgerEntry) -> Result<Option<Profile>, String> {
+        let mut profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        let mut ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+        let mut attended = self.attendance.lock().map_err(|err| err.to_string())?;
+        if attended.iter().any(|existing| existing.event_id == attendance.event_id && existing.discord_id == attendance.discord_id) {
+            return Ok(None);
+        }
+
+        attended.push(attendance);
+        let profile = profiles.entry(entry.discord_id.clone()).or_default();
+        profile.points += entry.points;
+        profile.credits += entry.credits;
+        ledger.push(entry);
+        Ok(Some(profile.clone()))
+    }
+
+    async fn get_attendance(&self, event_id: &str) -> Result<Vec<Attendance>, String> {
+        let attendance = self.attendance.lock().map_err(|err| err.to_string())?;
+        Ok(attendance.iter().filter(|attendance| attendance.event_id == event_id).cloned().collect())
+    }
+
+    async fn put_claim(&self, claim: Claim) -> Result<Claim, String> {
+        let mut claims = self.claims.lock().map_err(|err| err.to_string())?;
+        claims.retain(|existing| existing.id != claim.id);
+        claims.push(claim.clone());
+        Ok(claim)
+    }
+
+    async fn get_claim(&self, id: &str) -> Result<Option<Claim>, String> {
+        let claims = self.claims.lock().map_err(|err| err.to_string())?;
+        Ok(claims.iter().find(|claim| claim.id == id).cloned())
+    }
+
+    async fn get_claims_by_member(&self, user_id: &str) -> Result<Vec<Claim>, String> {
+        let claims = self.claims.lock().map_err(|err| err.to_string())?;
+        Ok(claims.iter().filter(|claim| claim.discord_id == user_id).cloned().collect())
+    }
+
+    async fn review_claim(&self, id: &str, status: ClaimStatus, reviewer_id: &str) -> Result<Option<Claim>, String> {
+        let mut claims = self.claims.lock().map_err(|err| err.to_string())?;
+        match claims.iter_mut().find(|claim| claim.id == id && claim.status == ClaimStatus::Pending) {
+            Some(claim) => {
+                claim.status = status;
+                claim.reviewer_id = Some(reviewer_id.to_string());
+                Ok(Some(claim.clone()))
+            },
+            None => Ok(None)
+        }
+    }
+
+    async fn approve_claim(&self, id: &str, reviewer_id: &str, entry: LedgerEntry) -> Result<Option<(Claim, Profile)>, String> {
+        let mut profiles = self.profiles.lock().map_err(|err| err.to_string())?;
+        let mut ledger = self.ledger.lock().map_err(|err| err.to_string())?;
+        let mut claims = self.claims.lock().map_err(|err| err.to_string())?;
+        let claim = match claims.iter_mut().find(|claim| claim.id == id && claim.status == ClaimStatus::Pending) {
+            Some(claim) => claim,
+            None => return Ok(None)
+        };
+
+        claim.status = ClaimStatus::Approved;
+        claim.reviewer_id = Some(reviewer_id.to_string());
+        let profile = profiles.entry(entry.discord_id.clone()).or_default();
+        profile.points += entry.points;
+        profile.credits += entry.credits;
+        ledger.push(entry);
+        Ok(Some((claim.clone(), profile.clone())))
+    }
+}
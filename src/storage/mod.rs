@@ -0,0 +1,429 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serenity::async_trait;
+use serenity::client::Context;
+use serenity::prelude::TypeMapKey;
+
+use crate::config::Config;
+
+pub mod dynamodb;
+pub mod memory;
+pub mod sqlite;
+
+pub use self::dynamodb::DynamoStorage;
+pub use self::memory::MemoryStorage;
+pub use self::sqlite::SqliteStorage;
+
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+  pub points: i64,
+  pub credits: i64
+}
+
+#[derive(Clone, Debug)]
+pub struct Product {
+  pub name: String,
+  pub price: i64,
+  pub quantity: i64,
+  pub key: String,
+  pub description: String,
+  /// Most of it any one member may buy, or 0 for no limit
+  pub member_limit: i64,
+  /// Never runs out, so `quantity` is ignored
+  pub unlimited: bool,
+  /// Sold as codes from a pool, one per purchase, and `quantity` is how many are left
+  pub digital: bool
+}
+
+/// Changes to a product's fields; `None` leaves a field as it is.
+#[derive(Clone, Debug, Default)]
+pub struct ProductChanges {
+  pub name: Option<String>,
+  pub description: Option<String>,
+  pub price: Option<i64>,
+  pub quantity: Option<i64>,
+  pub member_limit: Option<i64>,
+  pub unlimited: Option<bool>
+}
+
+impl ProductChanges {
+    pub fn apply(self, product: &mut Product) {
+        if let Some(name) = self.name {
+            product.name = name;
+        }
+        if let Some(description) = self.description {
+            product.description = description;
+        }
+        product.price = self.price.unwrap_or(product.price);
+        product.quantity = self.quantity.unwrap_or(product.quantity);
+        product.member_limit = self.member_limit.unwrap_or(product.member_limit);
+        product.unlimited = self.unlimited.unwrap_or(product.unlimited);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Purchase {
+  pub id: String,
+  pub product_key: String,
+  pub discord_id: String,
+  /// Credits the buyer was charged, or 0 for purchases made before this was recorded
+  pub price: i64,
+  /// Seconds since the unix epoch, or 0 for purchases made before this was recorded
+  pub timestamp: i64,
+  pub status: PurchaseStatus,
+  /// What the buyer got, for digital products. Filled in by the store like the price
+  pub code: Option<String>
+}
+
+impl Purchase {
+    /// Purchases made before prices were recorded read as free, so there's no telling what to
+    /// give back for them. They're also from before timestamps, which tells them apart from free products.
+    pub fn price_unknown(&self) -> bool {
+        self.price == 0 && self.timestamp == 0
+    }
+}
+
+/// Where an order is up to. Purchases are pending until an exec hands the product over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PurchaseStatus {
+    Pending,
+    Fulfilled,
+    /// Called off before it was handed over, giving the buyer their credits back like a refund
+    Cancelled,
+    /// The buyer got their credits back and the product went back in stock
+    Refunded
+}
+
+impl PurchaseStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PurchaseStatus::Pending => "pending",
+            PurchaseStatus::Fulfilled => "fulfilled",
+            PurchaseStatus::Cancelled => "cancelled",
+            PurchaseStatus::Refunded => "refunded"
+        }
+    }
+
+    /// Purchases recorded before statuses existed have none, and count as pending.
+    pub fn parse(status: Option<&str>) -> PurchaseStatus {
+        match status {
+            Some("fulfilled") => PurchaseStatus::Fulfilled,
+            Some("cancelled") => PurchaseStatus::Cancelled,
+            Some("refunded") => PurchaseStatus::Refunded,
+            _ => PurchaseStatus::Pending
+        }
+    }
+}
+
+/// A member asking for an activity's points, waiting on an exec to check their evidence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Claim {
+  pub id: String,
+  pub activity_id: String,
+  pub discord_id: String,
+  /// What the member sent to show they did it: a description, links and attachment URLs
+  pub evidence: String,
+  /// Seconds since the unix epoch
+  pub timestamp: i64,
+  pub status: ClaimStatus,
+  /// The exec that approved or rejected it
+  pub reviewer_id: Option<String>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClaimStatus {
+    Pending,
+    Approved,
+    Rejected
+}
+
+impl ClaimStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ClaimStatus::Pending => "pending",
+            ClaimStatus::Approved => "approved",
+            ClaimStatus::Rejected => "rejected"
+        }
+    }
+
+    pub fn parse(status: &str) -> ClaimStatus {
+        match status {
+            "approved" => ClaimStatus::Approved,
+            "rejected" => ClaimStatus::Rejected,
+            _ => ClaimStatus::Pending
+        }
+    }
+}
+
+/// Something members check in to with a code, like a workshop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+  /// Short name admins refer to it by
+  pub id: String,
+  pub name: String,
+  /// What members type to check in, in upper case
+  pub code: String,
+  /// Awarded to each member that checks in
+  pub points: i64,
+  /// Seconds since the unix epoch after which the code stops working
+  pub expires: i64,
+  /// Seconds since the unix epoch
+  pub timestamp: i64
+}
+
+/// A member being at an event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attendance {
+  pub event_id: String,
+  pub discord_id: String,
+  /// Seconds since the unix epoch
+  pub timestamp: i64
+}
+
+/// One change to a member's balance. Every award and spend is appended to the
+/// ledger, so a member's profile can always be rebuilt from their entries.
+#[derive(Clone, Debug)]
+pub struct LedgerEntry {
+  pub id: String,
+  /// Who made the change
+  pub actor_id: String,
+  /// Whose balance changed
+  pub discord_id: String,
+  pub points: i64,
+  pub credits: i64,
+  pub reason: String,
+  /// Seconds since the unix epoch
+  pub timestamp: i64,
+  /// The message that caused the change
+  pub message_id: String
+}
+
+/// Seconds since the unix epoch, as stored on ledger entries.
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() as i64).unwrap_or(0)
+}
+
+/// Adds up a member's ledger entries into the profile they should have.
+pub fn balance(entries: &[LedgerEntry]) -> Profile {
+    entries.iter().fold(Profile::default(), |profile, entry| {
+        Profile { points: profile.points + entry.points, credits: profile.credits + entry.credits }
+    })
+}
+
+/// What a balance from before the ledger goes in as, so the ledger still adds up to it.
+pub fn opening_entry(user_id: &str, profile: &Profile) -> LedgerEntry {
+    LedgerEntry {
+        id: format!("opening-{}", user_id),
+        actor_id: String::new(),
+        discord_id: user_id.to_string(),
+        points: profile.points,
+        credits: profile.credits,
+        reason: "Opening balance, from before the ledger".to_string(),
+        timestamp: now(),
+        message_id: String::new()
+    }
+}
+
+/// Gives every member whose balance is older than the ledger an opening entry for it, so
+/// `audit` compares like with like. Run on startup; members already in the ledger are left
+/// alone. Returns how many were opened.
+pub async fn open_ledgers(storage: &dyn Storage) -> Result<usize, String> {
+    let mut opened = 0;
+    for (user_id, profile) in storage.get_profiles().await? {
+        if (profile.points, profile.credits) == (0, 0) || !storage.get_entries(&user_id).await?.is_empty() {
+            continue;
+        }
+        if storage.open_ledger(opening_entry(&user_id, &profile)).await? {
+            opened += 1;
+        }
+    }
+    Ok(opened)
+}
+
+/// The result of trying to move credits from one member to another.
+#[derive(Clone, Debug)]
+pub enum TransferOutcome {
+    /// Holds the sender's profile after the transfer
+    Transferred(Profile),
+    /// Holds how many credits the sender actually has
+    InsufficientCredits(i64)
+}
+
+/// The result of trying to buy a product.
+#[derive(Clone, Debug)]
+pub enum PurchaseOutcome {
+    /// `code` is the one taken from the pool for digital products
+    Purchased { product: Product, profile: Profile, code: Option<String> },
+    NoSuchProduct,
+    CannotAfford { product: Product, credits: i64 },
+    OutOfStock(Product),
+    /// The buyer already has as many as the product's `member_limit` allows
+    LimitReached(Product)
+}
+
+/// The result of trying to refund or cancel a purchase.
+#[derive(Clone, Debug)]
+pub enum RefundOutcome {
+    /// Holds the purchase as refunded or cancelled and the buyer's profile afterwards
+    Refunded { purchase: Purchase, profile: Profile },
+    NoSuchPurchase,
+    /// It was already paid back, or for cancelling, isn't pending any more
+    AlreadyClosed(Purchase),
+    /// It's from before prices were recorded, so what to give back isn't known
+    PriceUnknown(Purchase)
+}
+
+/// The result of trying to add an event.
+#[derive(Clone, Debug)]
+pub enum EventOutcome {
+    Added,
+    /// There's already an event with its id
+    IdTaken,
+    /// Holds the event still open with its check-in code
+    CodeInUse(Event)
+}
+
+/// Whether a purchase in `status` can still be paid back by moving it to `to`. Anything not
+/// paid back yet can be refunded, but only pending orders can be cancelled.
+pub fn can_pay_back(status: PurchaseStatus, to: PurchaseStatus) -> bool {
+    match to {
+        PurchaseStatus::Cancelled => status == PurchaseStatus::Pending,
+        _ => status == PurchaseStatus::Pending || status == PurchaseStatus::Fulfilled
+    }
+}
+
+/// Checks whether a member with `profile`, who has already bought `bought` of
+/// `product`, may buy another, returning why not if they can't.
+pub fn refuse_purchase(profile: &Profile, product: &Product, bought: i64) -> Option<PurchaseOutcome> {
+    if profile.credits < product.price {
+        Some(PurchaseOutcome::CannotAfford { product: product.clone(), credits: profile.credits })
+    }
+    else if !product.unlimited && product.quantity <= 0 {
+        Some(PurchaseOutcome::OutOfStock(product.clone()))
+    }
+    else if product.member_limit > 0 && bought >= product.member_limit {
+        Some(PurchaseOutcome::LimitReached(product.clone()))
+    }
+    else {
+        None
+    }
+}
+
+/// How many of `product_key` count towards a member's limit among their `purchases`.
+/// Refunded and cancelled orders don't.
+pub fn count_bought(purchases: &[Purchase], product_key: &str) -> i64 {
+    purchases.iter()
+        .filter(|purchase| purchase.product_key == product_key)
+        .filter(|purchase| purchase.status != PurchaseStatus::Refunded && purchase.status != PurchaseStatus::Cancelled)
+        .count() as i64
+}
+
+/// Everything the bot needs to persist: member profiles, store products and
+/// purchases. Commands only talk to this trait, so the backing store can be
+/// swapped without touching them.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Members that have never been given anything have an empty profile.
+    async fn get_profile(&self, user_id: &str) -> Result<Profile, String>;
+    /// Every member that has a profile, with their discord ID.
+    async fn get_profiles(&self) -> Result<Vec<(String, Profile)>, String>;
+    /// Atomically adds the entry's points and credits to its member's profile and appends it
+    /// to the ledger, creating the profile if needed. Returns the profile after the change.
+    async fn record_entry(&self, entry: LedgerEntry) -> Result<Profile, String>;
+    /// A member's ledger entries, oldest first.
+    async fn get_entries(&self, user_id: &str) -> Result<Vec<LedgerEntry>, String>;
+    /// Writes `entry` as the first in a member's ledger without touching their profile, for
+    /// balances from before the ledger. Only if their ledger is empty and their profile holds
+    /// exactly the entry's points and credits; returns whether it was written.
+    async fn open_ledger(&self, entry: LedgerEntry) -> Result<bool, String>;
+
+    /// Atomically records both sides of a transfer: `debit` takes credits from the sender
+    /// and `credit` gives them to the recipient. Nothing changes if the sender can't cover it.
+    async fn transfer(&self, debit: LedgerEntry, credit: LedgerEntry) -> Result<TransferOutcome, String>;
+
+    async fn get_store(&self) -> Result<Vec<Product>, String>;
+    async fn get_product(&self, product_key: &str) -> Result<Option<Product>, String>;
+    async fn put_product(&self, product: Product) -> Result<Product, String>;
+    /// Removes a product along with any codes it has left, so they can't be sold if its key is used again.
+    async fn delete_product(&self, key: &str) -> Result<String, String>;
+    /// Changes only the given fields of a product, returning it as updated, or `None` if there's no such product.
+    async fn edit_product(&self, key: &str, changes: ProductChanges) -> Result<Option<Product>, String>;
+    /// Atomically adds `amount` to a product's quantity, returning it as updated, or `None` if there's no such product.
+    async fn restock(&self, key: &str, amount: i64) -> Result<Option<Product>, String>;
+    /// Adds `codes` to a product's pool, making it digital and counting them into its quantity.
+    /// Codes already in the pool are skipped. Returns the product as updated, or `None` if there's no such product.
+    async fn add_codes(&self, key: &str, codes: &[String]) -> Result<Option<Product>, String>;
+
+    /// Records `purchase`, takes one of the product out of stock and charges the
+    /// buyer its price as a single all-or-nothing operation. The purchase's price and
+    /// `entry`'s credits are set to what the buyer was charged, the latter as a negative amount.
+    async fn buy(&self, purchase: Purchase, entry: LedgerEntry) -> Result<PurchaseOutcome, String>;
+    /// Marks a purchase refunded, gives the buyer back what they paid and puts the product
+    /// back in stock if it's still in the store, as a single all-or-nothing operation.
+    /// Digital products aren't restocked, since the buyer has seen the code.
+    /// `entry` goes in the buyer's ledger with its credits set to the amount returned.
+    async fn refund(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String>;
+    /// The same as `refund`, but only for pending orders, which are marked cancelled instead.
+    async fn cancel(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String>;
+
+    async fn get_purchase(&self, id: &str) -> Result<Option<Purchase>, String>;
+    /// Everything a member has bought, in no particular order.
+    async fn get_purchases_by_member(&self, user_id: &str) -> Result<Vec<Purchase>, String>;
+    /// Every purchase with `status`, in no particular order.
+    async fn get_purchases_by_status(&self, status: PurchaseStatus) -> Result<Vec<Purchase>, String>;
+    /// Moves a purchase from `from` to `to`, returning it as updated. `None` if there's
+    /// no such purchase or its status isn't `from` any more, in which case nothing changes.
+    async fn update_purchase_status(&self, id: &str, from: PurchaseStatus, to: PurchaseStatus) -> Result<Option<Purchase>, String>;
+
+    /// Adds a new event, unless there's already one with its id or its code is taken by an event
+    /// still open at its `timestamp`, in which case nothing changes.
+    async fn add_event(&self, event: Event) -> Result<EventOutcome, String>;
+    async fn get_event(&self, id: &str) -> Result<Option<Event>, String>;
+    /// Every event, in no particular order.
+    async fn get_events(&self) -> Result<Vec<Event>, String>;
+    /// Records `attendance` and awards `entry` as a single all-or-nothing operation, returning
+    /// the member's profile afterwards. `None` if they're already down as attending, in which case nothing changes.
+    async fn check_in(&self, attendance: Attendance, entry: LedgerEntry) -> Result<Option<Profile>, String>;
+    /// Everyone at an event, in no particular order.
+    async fn get_attendance(&self, event_id: &str) -> Result<Vec<Attendance>, String>;
+
+    async fn put_claim(&self, claim: Claim) -> Result<Claim, String>;
+    async fn get_claim(&self, id: &str) -> Result<Option<Claim>, String>;
+    /// Everything a member has claimed, in no particular order.
+    async fn get_claims_by_member(&self, user_id: &str) -> Result<Vec<Claim>, String>;
+    /// Moves a pending claim to `status`, recording who reviewed it, and returns it as updated.
+    /// `None` if there's no such claim or it has already been reviewed, in which case nothing changes.
+    async fn review_claim(&self, id: &str, status: ClaimStatus, reviewer_id: &str) -> Result<Option<Claim>, String>;
+    /// Approves a pending claim and awards `entry` as a single all-or-nothing operation, returning the
+    /// claim as updated and the member's profile afterwards. `None` if there's no such claim or it has
+    /// already been reviewed, in which case nothing changes.
+    async fn approve_claim(&self, id: &str, reviewer_id: &str, entry: LedgerEntry) -> Result<Option<(Claim, Profile)>, String>;
+}
+
+pub struct StorageKey;
+
+impl TypeMapKey for StorageKey {
+    type Value = Arc<dyn Storage>;
+}
+
+/// Sets up the backend named in the config: `dynamodb`, `sqlite`, or `memory`
+/// which keeps everything in process for offline development.
+pub async fn from_config(config: &Config) -> Result<Arc<dyn Storage>, String> {
+    match config.storage.backend.as_str() {
+        "dynamodb" => {
+            // Like SQLite's, tables added since the last deploy are made before anything uses them
+            let storage = DynamoStorage::new(config.region()?, config.tables.clone());
+            storage.create_tables().await?;
+            Ok(Arc::new(storage))
+        },
+        "memory" => Ok(Arc::new(MemoryStorage::new())),
+        "sqlite" => Ok(Arc::new(SqliteStorage::open(&config.storage.sqlite_path)?)),
+        other => Err(format!("Unknown storage backend: {}", other))
+    }
+}
+
+pub async fn get(ctx: &Context) -> Arc<dyn Storage> {
+    let data = ctx.data.read().await;
+    data.get::<StorageKey>().expect("Storage has not been set up").clone()
+}
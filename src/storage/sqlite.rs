@@ -0,0 +1,656 @@
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+use serenity::async_trait;
+
+use super::{Storage, Attendance, Claim, ClaimStatus, Event, EventOutcome, Profile, Product, ProductChanges, Purchase, PurchaseOutcome, PurchaseStatus, RefundOutcome, TransferOutcome, LedgerEntry, can_pay_back, count_bought, refuse_purchase};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS TPCMemberPoints (
+        discord_id TEXT PRIMARY KEY,
+        points INTEGER NOT NULL DEFAULT 0,
+        credits INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS TPCStore (
+        key TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        price INTEGER NOT NULL,
+        quantity INTEGER NOT NULL,
+        member_limit INTEGER NOT NULL DEFAULT 0,
+        unlimited INTEGER NOT NULL DEFAULT 0,
+        digital INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS TPCPurchases (
+        id TEXT PRIMARY KEY,
+        product_key TEXT NOT NULL,
+        discord_id TEXT NOT NULL,
+        price INTEGER NOT NULL DEFAULT 0,
+        timestamp INTEGER NOT NULL DEFAULT 0,
+        status TEXT NOT NULL DEFAULT 'pending',
+        code TEXT
+    );
+    CREATE TABLE IF NOT EXISTS TPCLedger (
+        id TEXT PRIMARY KEY,
+        actor_id TEXT NOT NULL,
+        discord_id TEXT NOT NULL,
+        points INTEGER NOT NULL,
+        credits INTEGER NOT NULL,
+        reason TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        message_id TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS TPCCodes (
+        product_key TEXT NOT NULL,
+        code TEXT NOT NULL,
+        PRIMARY KEY (product_key, code)
+    );
+    CREATE TABLE IF NOT EXISTS TPCClaims (
+        id TEXT PRIMARY KEY,
+        activity_id TEXT NOT NULL,
+        discord_id TEXT NOT NULL,
+        evidence TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        reviewer_id TEXT
+    );
+    CREATE TABLE IF NOT EXISTS TPCEvents (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        code TEXT NOT NULL,
+        points INTEGER NOT NULL,
+        expires INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS TPCAttendance (
+        event_id TEXT NOT NULL,
+        discord_id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        PRIMARY KEY (event_id, discord_id)
+    );
+    CREATE INDEX IF NOT EXISTS TPCLedgerByMember ON TPCLedger (discord_id, timestamp);
+    CREATE INDEX IF NOT EXISTS TPCPurchasesByMember ON TPCPurchases (discord_id);
+    CREATE INDEX IF NOT EXISTS TPCClaimsByMember ON TPCClaims (discord_id);
+";
+
+/// Columns added since the tables were first created, as `(table, column, definition)`.
+/// Databases made before then get them added on startup.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("TPCPurchases", "status", "TEXT NOT NULL DEFAULT 'pending'"),
+    ("TPCPurchases", "price", "INTEGER NOT NULL DEFAULT 0"),
+    ("TPCPurchases", "timestamp", "INTEGER NOT NULL DEFAULT 0"),
+    ("TPCStore", "member_limit", "INTEGER NOT NULL DEFAULT 0"),
+    ("TPCStore", "unlimited", "INTEGER NOT NULL DEFAULT 0"),
+    ("TPCStore", "digital", "INTEGER NOT NULL DEFAULT 0"),
+    ("TPCPurchases", "code", "TEXT")
+];
+
+/// Stores everything in a single SQLite file, for hosting the bot without AWS.
+/// The tables mirror the DynamoDB ones and are created on startup if missing.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<SqliteStorage, String> {
+        let conn = Connection::open(path).map_err(|err| err.to_string())?;
+        SqliteStorage::with_connection(conn)
+    }
+
+    pub fn with_connection(conn: Connection) -> Result<SqliteStorage, String> {
+        conn.execute_batch(SCHEMA).map_err(|err| err.to_string())?;
+        for (table, column, definition) in ADDED_COLUMNS {
+            add_missing_column(&conn, table, column, definition)?;
+        }
+        Ok(SqliteStorage { conn: Mutex::new(conn) })
+    }
+}
+
+fn add_missing_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), String> {
+    let mut statement = conn.prepare(&format!("PRAGMA table_info({})", table)).map_err(|err| err.to_string())?;
+    let columns = statement.query_map(params![], |row| row.get::<_, String>("name"))
+        .map_err(|err| err.to_string())?
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .map_err(|err| err.to_string())?;
+    if !columns.iter().any(|name| name == column) {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+fn row_to_product(row: &Row) -> rusqlite::Result<Product> {
+    Ok(Product {
+        key: row.get("key")?,
+        name: row.get("name")?,
+        description: row.get("description")?,
+        price: row.get("price")?,
+        quantity: row.get("quantity")?,
+        member_limit: row.get("member_limit")?,
+        unlimited: row.get("unlimited")?,
+        digital: row.get("digital")?
+    })
+}
+
+fn row_to_purchase(row: &Row) -> rusqlite::Result<Purchase> {
+    let status: String = row.get("status")?;
+    Ok(Purchase {
+        id: row.get("id")?,
+        product_key: row.get("product_key")?,
+        discord_id: row.get("discord_id")?,
+        price: row.get("price")?,
+        timestamp: row.get("timestamp")?,
+        status: PurchaseStatus::parse(Some(&status)),
+        code: row.get("code")?
+    })
+}
+
+fn row_to_claim(row: &Row) -> rusqlite::Result<Claim> {
+    let status: String = row.get("status")?;
+    Ok(Claim {
+        id: row.get("id")?,
+        activity_id: row.get("activity_id")?,
+        discord_id: row.get("discord_id")?,
+        evidence: row.get("evidence")?,
+        timestamp: row.get("timestamp")?,
+        status: ClaimStatus::parse(&status),
+        reviewer_id: row.get("reviewer_id")?
+    })
+}
+
+fn row_to_event(row: &Row) -> rusqlite::Result<Event> {
+    Ok(Event {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        code: row.get("code")?,
+        points: row.get("points")?,
+        expires: row.get("expires")?,
+        timestamp: row.get("timestamp")?
+    })
+}
+
+fn row_to_attendance(row: &Row) -> rusqlite::Result<Attendance> {
+    Ok(Attendance {
+        event_id: row.get("event_id")?,
+        discord_id: row.get("discord_id")?,
+        timestamp: row.get("timestamp")?
+    })
+}
+
+fn row_to_entry(row: &Row) -> rusqlite::Result<LedgerEntry> {
+    Ok(LedgerEntry {
+        id: row.get("id")?,
+        actor_id: row.get("actor_id")?,
+        discord_id: row.get("discord_id")?,
+        points: row.get("points")?,
+        credits: row.get("credits")?,
+        reason: row.get("reason")?,
+        timestamp: row.get("timestamp")?,
+        message_id: row.get("message_id")?
+    })
+}
+
+fn insert_entry(conn: &Connection, entry: &LedgerEntry) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO TPCLedger (id, actor_id, discord_id, points, credits, reason, timestamp, message_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![entry.id, entry.actor_id, entry.discord_id, entry.points, entry.credits, entry.reason, entry.timestamp, entry.message_id]
+    ).map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+/// Adds the entry's points and credits to its member's profile and appends it to the
+/// ledger, returning the profile afterwards. Run it inside a transaction.
+fn apply_entry(conn: &Connection, entry: &LedgerEntry) -> Result<Profile, String> {
+    conn.execute(
+        "INSERT INTO TPCMemberPoints (discord_id, points, credits) VALUES (?1, ?2, ?3)
+         ON CONFLICT(discord_id) DO UPDATE SET points = points + excluded.points, credits = credits + excluded.credits",
+        params![entry.discord_id, entry.points, entry.credits]
+    ).map_err(|err| err.to_string())?;
+    insert_entry(conn, entry)?;
+    conn.query_row(
+        "SELECT points, credits FROM TPCMemberPoints WHERE discord_id = ?1",
+        params![entry.discord_id],
+        |row| Ok(Profile { points: row.get(0)?, credits: row.get(1)? })
+    ).map_err(|err| err.to_string())
+}
+
+/// Refunds or cancels a purchase, depending on `to`.
+fn pay_back(conn: &mut Connection, id: &str, to: PurchaseStatus, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+    let transaction = conn.transaction().map_err(|err| err.to_string())?;
+
+    let purchase = transaction.query_row("SELECT * FROM TPCPurchases WHERE id = ?1", params![id], row_to_purchase)
+        .optional()
+        .map_err(|err| err.to_string())?;
+    let purchase = match purchase {
+        Some(purchase) if !can_pay_back(purchase.status, to) => return Ok(RefundOutcome::AlreadyClosed(purchase)),
+        Some(purchase) if purchase.price_unknown() => return Ok(RefundOutcome::PriceUnknown(purchase)),
+        Some(purchase) => Purchase { status: to, ..purchase },
+        None => return Ok(RefundOutcome::NoSuchPurchase)
+    };
+
+    transaction.execute("UPDATE TPCPurchases SET status = ?1 WHERE id = ?2", params![purchase.status.as_str(), purchase.id])
+        .map_err(|err| err.to_string())?;
+    // Does nothing if the product has since been deleted, or its codes can't be taken back
+    transaction.execute("UPDATE TPCStore SET quantity = quantity + 1 WHERE key = ?1 AND digital = 0", params![purchase.product_key])
+        .map_err(|err| err.to_string())?;
+    transaction.execute(
+        "INSERT INTO TPCMemberPoints (discord_id, points, credits) VALUES (?1, 0, ?2)
+         ON CONFLICT(discord_id) DO UPDATE SET credits = credits + excluded.credits",
+        params![purchase.discord_id, purchase.price]
+    ).map_err(|err| err.to_string())?;
+    insert_entry(&transaction, &LedgerEntry { discord_id: purchase.discord_id.clone(), points: 0, credits: purchase.price, ..entry })?;
+    let profile = transaction.query_row(
+        "SELECT points, credits FROM TPCMemberPoints WHERE discord_id = ?1",
+        params![purchase.discord_id],
+        |row| Ok(Profile { points: row.get(0)?, credits: row.get(1)? })
+    ).map_err(|err| err.to_string())?;
+    transaction.commit().map_err(|err| err.to_string())?;
+
+    Ok(RefundOutcome::Refunded { purchase, profile })
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn get_profile(&self, user_id: &str) -> Result<Profile, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        conn.query_row(
+            "SELECT points, credits FROM TPCMemberPoints WHERE discord_id = ?1",
+            params![user_id],
+            |row| Ok(Profile { points: row.get(0)?, credits: row.get(1)? })
+        ).optional()
+            .map(|profile| profile.unwrap_or_default())
+            .map_err(|err| err.to_string())
+    }
+
+    async fn get_profiles(&self) -> Result<Vec<(String, Profile)>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT discord_id, points, credits FROM TPCMemberPoints")
+            .map_err(|err| err.to_string())?;
+        let profiles = statement.query_map(params![], |row| {
+            Ok((row.get(0)?, Profile { points: row.get(1)?, credits: row.get(2)? }))
+        }).map_err(|err| err.to_string())?;
+        profiles.collect::<rusqlite::Result<Vec<(String, Profile)>>>().map_err(|err| err.to_string())
+    }
+
+    async fn record_entry(&self, entry: LedgerEntry) -> Result<Profile, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+        let profile = apply_entry(&transaction, &entry)?;
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(profile)
+    }
+
+    async fn get_entries(&self, user_id: &str) -> Result<Vec<LedgerEntry>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT * FROM TPCLedger WHERE discord_id = ?1 ORDER BY timestamp")
+            .map_err(|err| err.to_string())?;
+        let entries = statement.query_map(params![user_id], row_to_entry).map_err(|err| err.to_string())?;
+        entries.collect::<rusqlite::Result<Vec<LedgerEntry>>>().map_err(|err| err.to_string())
+    }
+
+    async fn open_ledger(&self, entry: LedgerEntry) -> Result<bool, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+
+        let entries: i64 = transaction.query_row("SELECT COUNT(*) FROM TPCLedger WHERE discord_id = ?1", params![entry.discord_id], |row| row.get(0))
+            .map_err(|err| err.to_string())?;
+        let profile = transaction.query_row(
+            "SELECT points, credits FROM TPCMemberPoints WHERE discord_id = ?1",
+            params![entry.discord_id],
+            |row| Ok(Profile { points: row.get(0)?, credits: row.get(1)? })
+        ).optional()
+            .map_err(|err| err.to_string())?
+            .unwrap_or_default();
+        if entries > 0 || (profile.points, profile.credits) != (entry.points, entry.credits) {
+            return Ok(false);
+        }
+        insert_entry(&transaction, &entry)?;
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(true)
+    }
+
+    async fn transfer(&self, debit: LedgerEntry, credit: LedgerEntry) -> Result<TransferOutcome, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+
+        let sender_credits: i64 = transaction.query_row(
+            "SELECT credits FROM TPCMemberPoints WHERE discord_id = ?1",
+            params![debit.discord_id],
+            |row| row.get(0)
+        ).optional()
+            .map_err(|err| err.to_string())?
+            .unwrap_or(0);
+        if sender_credits + debit.credits < 0 {
+            return Ok(TransferOutcome::InsufficientCredits(sender_credits));
+        }
+
+        for entry in [&debit, &credit].iter() {
+            transaction.execute(
+                "INSERT INTO TPCMemberPoints (discord_id, points, credits) VALUES (?1, 0, ?2)
+                 ON CONFLICT(discord_id) DO UPDATE SET credits = credits + excluded.credits",
+                params![entry.discord_id, entry.credits]
+            ).map_err(|err| err.to_string())?;
+            insert_entry(&transaction, entry)?;
+        }
+        let sender = transaction.query_row(
+            "SELECT points, credits FROM TPCMemberPoints WHERE discord_id = ?1",
+            params![debit.discord_id],
+            |row| Ok(Profile { points: row.get(0)?, credits: row.get(1)? })
+        ).map_err(|err| err.to_string())?;
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(TransferOutcome::Transferred(sender))
+    }
+
+    async fn get_store(&self) -> Result<Vec<Product>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT * FROM TPCStore").map_err(|err| err.to_string())?;
+        let products = statement.query_map(params![], row_to_product).map_err(|err| err.to_string())?;
+        products.collect::<rusqlite::Result<Vec<Product>>>().map_err(|err| err.to_string())
+    }
+
+    async fn get_product(&self, product_key: &str) -> Result<Option<Product>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        conn.query_row("SELECT * FROM TPCStore WHERE key = ?1", params![product_key], row_to_product)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn put_product(&self, product: Product) -> Result<Product, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO TPCStore (key, name, description, price, quantity, member_limit, unlimited, digital)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![product.key, product.name, product.description, product.price, product.quantity,
+                    product.member_limit, product.unlimited, product.digital]
+        ).map_err(|err| err.to_string())?;
+        Ok(product)
+    }
+
+    async fn delete_product(&self, key: &str) -> Result<String, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+        transaction.execute("DELETE FROM TPCStore WHERE key = ?1", params![key]).map_err(|err| err.to_string())?;
+        transaction.execute("DELETE FROM TPCCodes WHERE product_key = ?1", params![key]).map_err(|err| err.to_string())?;
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(key.to_string())
+    }
+
+    async fn edit_product(&self, key: &str, changes: ProductChanges) -> Result<Option<Product>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let updated = conn.execute(
+            "UPDATE TPCStore SET name = COALESCE(?1, name), description = COALESCE(?2, description),
+                                 price = COALESCE(?3, price), quantity = COALESCE(?4, quantity),
+                                 member_limit = COALESCE(?5, member_limit), unlimited = COALESCE(?6, unlimited)
+             WHERE key = ?7",
+            params![changes.name, changes.description, changes.price, changes.quantity, changes.member_limit, changes.unlimited, key]
+        ).map_err(|err| err.to_string())?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        conn.query_row("SELECT * FROM TPCStore WHERE key = ?1", params![key], row_to_product)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn restock(&self, key: &str, amount: i64) -> Result<Option<Product>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let updated = conn.execute("UPDATE TPCStore SET quantity = quantity + ?1 WHERE key = ?2", params![amount, key])
+            .map_err(|err| err.to_string())?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        conn.query_row("SELECT * FROM TPCStore WHERE key = ?1", params![key], row_to_product)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn add_codes(&self, key: &str, codes: &[String]) -> Result<Option<Product>, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+
+        let exists = transaction.query_row("SELECT 1 FROM TPCStore WHERE key = ?1", params![key], |_| Ok(()))
+            .optional()
+            .map_err(|err| err.to_string())?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+        let mut added = 0;
+        for code in codes {
+            added += transaction.execute("INSERT OR IGNORE INTO TPCCodes (product_key, code) VALUES (?1, ?2)", params![key, code])
+                .map_err(|err| err.to_string())?;
+        }
+        transaction.execute(
+            "UPDATE TPCStore SET quantity = quantity + ?1, digital = 1, unlimited = 0 WHERE key = ?2",
+            params![added as i64, key]
+        ).map_err(|err| err.to_string())?;
+        let product = transaction.query_row("SELECT * FROM TPCStore WHERE key = ?1", params![key], row_to_product)
+            .map_err(|err| err.to_string())?;
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(Some(product))
+    }
+
+    async fn buy(&self, purchase: Purchase, entry: LedgerEntry) -> Result<PurchaseOutcome, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+
+        let product = transaction.query_row("SELECT * FROM TPCStore WHERE key = ?1", params![purchase.product_key], row_to_product)
+            .optional()
+            .map_err(|err| err.to_string())?;
+        let product = match product {
+            Some(product) => product,
+            None => return Ok(PurchaseOutcome::NoSuchProduct)
+        };
+        let profile = transaction.query_row(
+            "SELECT points, credits FROM TPCMemberPoints WHERE discord_id = ?1",
+            params![purchase.discord_id],
+            |row| Ok(Profile { points: row.get(0)?, credits: row.get(1)? })
+        ).optional()
+            .map_err(|err| err.to_string())?
+            .unwrap_or_default();
+        let bought = {
+            let mut statement = transaction.prepare("SELECT * FROM TPCPurchases WHERE discord_id = ?1 AND product_key = ?2")
+                .map_err(|err| err.to_string())?;
+            let bought = statement.query_map(params![purchase.discord_id, product.key], row_to_purchase).map_err(|err| err.to_string())?;
+            bought.collect::<rusqlite::Result<Vec<Purchase>>>().map_err(|err| err.to_string())?
+        };
+        if let Some(refusal) = refuse_purchase(&profile, &product, count_bought(&bought, &product.key)) {
+            return Ok(refusal);
+        }
+        let code = if product.digital {
+            let code = transaction.query_row(
+                "SELECT code FROM TPCCodes WHERE product_key = ?1 ORDER BY rowid LIMIT 1",
+                params![product.key],
+                |row| row.get::<_, String>(0)
+            ).optional()
+                .map_err(|err| err.to_string())?;
+            match code {
+                Some(code) => {
+                    transaction.execute("DELETE FROM TPCCodes WHERE product_key = ?1 AND code = ?2", params![product.key, code])
+                        .map_err(|err| err.to_string())?;
+                    Some(code)
+                },
+                None => return Ok(PurchaseOutcome::OutOfStock(product))
+            }
+        } else {
+            None
+        };
+
+        let quantity = if product.unlimited { product.quantity } else { product.quantity - 1 };
+        let product = Product { quantity, ..product };
+        let profile = Profile { credits: profile.credits - product.price, ..profile };
+        transaction.execute("UPDATE TPCStore SET quantity = ?1 WHERE key = ?2", params![product.quantity, product.key])
+            .map_err(|err| err.to_string())?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO TPCMemberPoints (discord_id, points, credits) VALUES (?1, ?2, ?3)",
+            params![purchase.discord_id, profile.points, profile.credits]
+        ).map_err(|err| err.to_string())?;
+        transaction.execute(
+            "INSERT INTO TPCPurchases (id, product_key, discord_id, price, timestamp, status, code) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![purchase.id, purchase.product_key, purchase.discord_id, product.price, purchase.timestamp, purchase.status.as_str(), code]
+        ).map_err(|err| err.to_string())?;
+        insert_entry(&transaction, &LedgerEntry { points: 0, credits: -product.price, ..entry })?;
+        transaction.commit().map_err(|err| err.to_string())?;
+
+        Ok(PurchaseOutcome::Purchased { product, profile, code })
+    }
+
+    async fn refund(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        pay_back(&mut conn, id, PurchaseStatus::Refunded, entry)
+    }
+
+    async fn cancel(&self, id: &str, entry: LedgerEntry) -> Result<RefundOutcome, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        pay_back(&mut conn, id, PurchaseStatus::Cancelled, entry)
+    }
+
+    async fn get_purchase(&self, id: &str) -> Result<Option<Purchase>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        conn.query_row("SELECT * FROM TPCPurchases WHERE id = ?1", params![id], row_to_purchase)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn get_purchases_by_member(&self, user_id: &str) -> Result<Vec<Purchase>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT * FROM TPCPurchases WHERE discord_id = ?1").map_err(|err| err.to_string())?;
+        let purchases = statement.query_map(params![user_id], row_to_purchase).map_err(|err| err.to_string())?;
+        purchases.collect::<rusqlite::Result<Vec<Purchase>>>().map_err(|err| err.to_string())
+    }
+
+    async fn get_purchases_by_status(&self, status: PurchaseStatus) -> Result<Vec<Purchase>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT * FROM TPCPurchases WHERE status = ?1").map_err(|err| err.to_string())?;
+        let purchases = statement.query_map(params![status.as_str()], row_to_purchase).map_err(|err| err.to_string())?;
+        purchases.collect::<rusqlite::Result<Vec<Purchase>>>().map_err(|err| err.to_string())
+    }
+
+    async fn update_purchase_status(&self, id: &str, from: PurchaseStatus, to: PurchaseStatus) -> Result<Option<Purchase>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let updated = conn.execute(
+            "UPDATE TPCPurchases SET status = ?1 WHERE id = ?2 AND status = ?3",
+            params![to.as_str(), id, from.as_str()]
+        ).map_err(|err| err.to_string())?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        conn.query_row("SELECT * FROM TPCPurchases WHERE id = ?1", params![id], row_to_purchase)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn add_event(&self, event: Event) -> Result<EventOutcome, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        // Immediate, so no other connection to the file can add an event between the check and the insert
+        let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate).map_err(|err| err.to_string())?;
+        let clash = transaction.query_row(
+            "SELECT * FROM TPCEvents WHERE code = ?1 AND expires > ?2",
+            params![event.code, event.timestamp],
+            row_to_event
+        ).optional().map_err(|err| err.to_string())?;
+        if let Some(clash) = clash {
+            return Ok(EventOutcome::CodeInUse(clash));
+        }
+        let added = transaction.execute(
+            "INSERT OR IGNORE INTO TPCEvents (id, name, code, points, expires, timestamp) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![event.id, event.name, event.code, event.points, event.expires, event.timestamp]
+        ).map_err(|err| err.to_string())?;
+        if added == 0 {
+            return Ok(EventOutcome::IdTaken);
+        }
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(EventOutcome::Added)
+    }
+
+    async fn get_event(&self, id: &str) -> Result<Option<Event>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        conn.query_row("SELECT * FROM TPCEvents WHERE id = ?1", params![id], row_to_event)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn get_events(&self) -> Result<Vec<Event>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT * FROM TPCEvents").map_err(|err| err.to_string())?;
+        let events = statement.query_map(params![], row_to_event).map_err(|err| err.to_string())?;
+        events.collect::<rusqlite::Result<Vec<Event>>>().map_err(|err| err.to_string())
+    }
+
+    async fn check_in(&self, attendance: Attendance, entry: LedgerEntry) -> Result<Option<Profile>, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+        let added = transaction.execute(
+            "INSERT OR IGNORE INTO TPCAttendance (event_id, discord_id, timestamp) VALUES (?1, ?2, ?3)",
+            params![attendance.event_id, attendance.discord_id, attendance.timestamp]
+        ).map_err(|err| err.to_string())?;
+        if added == 0 {
+            return Ok(None);
+        }
+        let profile = apply_entry(&transaction, &entry)?;
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(Some(profile))
+    }
+
+    async fn get_attendance(&self, event_id: &str) -> Result<Vec<Attendance>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT * FROM TPCAttendance WHERE event_id = ?1").map_err(|err| err.to_string())?;
+        let attendance = statement.query_map(params![event_id], row_to_attendance).map_err(|err| err.to_string())?;
+        attendance.collect::<rusqlite::Result<Vec<Attendance>>>().map_err(|err| err.to_string())
+    }
+
+    async fn put_claim(&self, claim: Claim) -> Result<Claim, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO TPCClaims (id, activity_id, discord_id, evidence, timestamp, status, reviewer_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![claim.id, claim.activity_id, claim.discord_id, claim.evidence, claim.timestamp, claim.status.as_str(), claim.reviewer_id]
+        ).map_err(|err| err.to_string())?;
+        Ok(claim)
+    }
+
+    async fn get_claim(&self, id: &str) -> Result<Option<Claim>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        conn.query_row("SELECT * FROM TPCClaims WHERE id = ?1", params![id], row_to_claim)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn get_claims_by_member(&self, user_id: &str) -> Result<Vec<Claim>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let mut statement = conn.prepare("SELECT * FROM TPCClaims WHERE discord_id = ?1").map_err(|err| err.to_string())?;
+        let claims = statement.query_map(params![user_id], row_to_claim).map_err(|err| err.to_string())?;
+        claims.collect::<rusqlite::Result<Vec<Claim>>>().map_err(|err| err.to_string())
+    }
+
+    async fn review_claim(&self, id: &str, status: ClaimStatus, reviewer_id: &str) -> Result<Option<Claim>, String> {
+        let conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let updated = conn.execute(
+            "UPDATE TPCClaims SET status = ?1, reviewer_id = ?2 WHERE id = ?3 AND status = ?4",
+            params![status.as_str(), reviewer_id, id, ClaimStatus::Pending.as_str()]
+        ).map_err(|err| err.to_string())?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        conn.query_row("SELECT * FROM TPCClaims WHERE id = ?1", params![id], row_to_claim)
+            .optional()
+            .map_err(|err| err.to_string())
+    }
+
+    async fn approve_claim(&self, id: &str, reviewer_id: &str, entry: LedgerEntry) -> Result<Option<(Claim, Profile)>, String> {
+        let mut conn = self.conn.lock().map_err(|err| err.to_string())?;
+        let transaction = conn.transaction().map_err(|err| err.to_string())?;
+        let updated = transaction.execute(
+            "UPDATE TPCClaims SET status = ?1, reviewer_id = ?2 WHERE id = ?3 AND status = ?4",
+            params![ClaimStatus::Approved.as_str(), reviewer_id, id, ClaimStatus::Pending.as_str()]
+        ).map_err(|err| err.to_string())?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        let profile = apply_entry(&transaction, &entry)?;
+        let claim = transaction.query_row("SELECT * FROM TPCClaims WHERE id = ?1", params![id], row_to_claim)
+            .map_err(|err| err.to_string())?;
+        transaction.commit().map_err(|err| err.to_string())?;
+        Ok(Some((claim, profile)))
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use serenity::builder::{CreateComponents, CreateEmbed};
+use serenity::client::Context;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use serenity::Error;
+
+use leadershipdiscordbot_rs::commands::{CommandContext, Reply, Response};
+use leadershipdiscordbot_rs::commands::store::{self, BuyCheck, StorePage};
+use leadershipdiscordbot_rs::config;
+
+use crate::{pages, reply};
+
+const PREVIOUS: &str = "store_previous";
+const NEXT: &str = "store_next";
+const SELECT: &str = "store_select";
+const BUY: &str = "store_buy";
+const CONFIRM: &str = "store_confirm";
+const CANCEL: &str = "store_cancel";
+
+/// How long the store stays interactive after the last click.
+const STORE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long someone has to confirm a purchase.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where the store is up to for whoever opened it.
+pub struct StoreState {
+    pub pages: Vec<StorePage>,
+    pub index: usize,
+    /// Key of the product picked from the menu on this page
+    pub selected: Option<String>
+}
+
+impl StoreState {
+    pub fn new(pages: Vec<StorePage>) -> StoreState {
+        StoreState { pages, index: 0, selected: None }
+    }
+
+    pub fn embed<'a>(&self, e: &'a mut CreateEmbed, colour: u64) -> &'a mut CreateEmbed {
+        let responses: Vec<Response> = self.pages.iter().map(|page| page.response.clone()).collect();
+        pages::page(e, &responses, self.index, colour)
+    }
+
+    /// A menu of the products on this page, then buttons to flip pages and buy the picked product.
+    pub fn components<'a>(&self, c: &'a mut CreateComponents) -> &'a mut CreateComponents {
+        let products = &self.pages[self.index].products;
+        c.create_action_row(|row| row.create_select_menu(|menu| {
+            menu.custom_id(SELECT).placeholder("Pick a product").options(|options| {
+                for product in products {
+                    options.create_option(|option| {
+                        option.label(store::label(&product.name))
+                            .value(&product.key)
+                            .description(store::label(&format!("{} gems, {}", product.price, store::show_stock(product))))
+                            .default_selection(self.selected.as_deref() == Some(product.key.as_str()))
+                    });
+                }
+                options
+            })
+        }));
+        c.create_action_row(|row| {
+            row.create_button(|b| b.custom_id(PREVIOUS).label("Previous").style(ButtonStyle::Secondary).disabled(self.pages.len() <= 1))
+                .create_button(|b| b.custom_id(NEXT).label("Next").style(ButtonStyle::Secondary).disabled(self.pages.len() <= 1))
+                .create_button(|b| b.custom_id(BUY).label("Buy").style(ButtonStyle::Success).disabled(self.selected.is_none()))
+        })
+    }
+}
+
+/// Lets `author_id` browse and buy from the store shown on `message` until they
+/// stop for a while, then takes the buttons away.
+pub async fn browse(ctx: &Context, command: &CommandContext<'_>, message: Message, author_id: UserId, mut state: StoreState) -> Result<(), Error> {
+    let colour = config::get(ctx).await.colour();
+    while let Some(interaction) = message.await_component_interaction(ctx)
+        .author_id(author_id)
+        .timeout(STORE_TIMEOUT)
+        .await {
+        match interaction.data.custom_id.as_str() {
+            PREVIOUS => {
+                state.index = (state.index + state.pages.len() - 1) % state.pages.len();
+                state.selected = None;
+            },
+            NEXT => {
+                state.index = (state.index + 1) % state.pages.len();
+                state.selected = None;
+            },
+            SELECT => {
+                state.selected = interaction.data.values.first().cloned();
+            },
+            BUY => {
+                // Every click needs answering, or Discord says the interaction failed
+                match &state.selected {
+                    Some(key) => buy(ctx, command, &interaction, key, colour).await?,
+                    None => {
+                        let reply = Response::error("Nothing picked", "Pick a product from the menu first").into();
+                        respond_privately(ctx, &interaction, reply, colour).await?
+                    }
+                }
+                continue;
+            },
+            _ => continue
+        }
+
+        interaction.create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.embed(|e| state.embed(e, colour)).components(|c| state.components(c)))
+        }).await?;
+    }
+
+    message.clone().edit(&ctx, |m| m.components(|c| c)).await
+}
+
+/// Asks whoever pressed Buy to confirm, privately, then buys it the same way `buy` does.
+async fn buy(ctx: &Context, command: &CommandContext<'_>, interaction: &MessageComponentInteraction, key: &str, colour: u64) -> Result<(), Error> {
+    let prompt = match store::check_buy(command, key).await {
+        Ok(BuyCheck::NeedsConfirmation { prompt, .. }) => prompt,
+        Ok(BuyCheck::Refused(reply)) => return respond_privately(ctx, interaction, reply, colour).await,
+        Err(err) => {
+            println!("Error checking purchase: {:?}", err);
+            return respond_privately(ctx, interaction, Response::error("Something went wrong", "Please try again later").into(), colour).await;
+        }
+    };
+
+    interaction.create_interaction_response(&ctx.http, |r| {
+        r.kind(InteractionResponseType::ChannelMessageWithSource)
+            .interaction_response_data(|d| {
+                d.ephemeral(true)
+                    .embed(|e| reply::embed(e, &prompt, colour))
+                    .components(|c| c.create_action_row(|row| {
+                        row.create_button(|b| b.custom_id(CONFIRM).label("Confirm").style(ButtonStyle::Success))
+                            .create_button(|b| b.custom_id(CANCEL).label("Cancel").style(ButtonStyle::Danger))
+                    }))
+            })
+    }).await?;
+    let confirmation = interaction.get_interaction_response(&ctx.http).await?;
+
+    let answer = confirmation.await_component_interaction(ctx)
+        .author_id(interaction.user.id)
+        .timeout(CONFIRM_TIMEOUT)
+        .await;
+
+    let result = match &answer {
+        Some(answer) if answer.data.custom_id == CONFIRM => {
+            store::buy(command, &[key.to_string()]).await.unwrap_or_else(|err| {
+                println!("Error buying {}: {:?}", key, err);
+                Response::error("Something went wrong", "Please try again later").into()
+            })
+        },
+        // Cancelling or not answering in time counts as no
+        _ => store::cancelled_buy()
+    };
+    if let Some(answer) = &answer {
+        answer.defer(&ctx.http).await?;
+    }
+    interaction.edit_original_interaction_response(&ctx.http, |m| {
+        if let Some(response) = first_response(result) {
+            m.embed(|e| reply::embed(e, &response, colour));
+        }
+        m.components(|c| c)
+    }).await?;
+    Ok(())
+}
+
+/// The one embed to show for a reply, where there's no room for pages.
+fn first_response(reply: Reply) -> Option<Response> {
+    match reply {
+        Reply::Nothing => None,
+        Reply::Embed(response) => Some(response),
+        Reply::Pages(responses) => responses.into_iter().next(),
+        Reply::File(response, _) => Some(response)
+    }
+}
+
+async fn respond_privately(ctx: &Context, interaction: &MessageComponentInteraction, reply: Reply, colour: u64) -> Result<(), Error> {
+    let response = first_response(reply).unwrap_or_else(|| Response::new("Nothing to show", ""));
+    interaction.create_interaction_response(&ctx.http, |r| {
+        r.kind(InteractionResponseType::ChannelMessageWithSource)
+            .interaction_response_data(|d| d.ephemeral(true).embed(|e| reply::embed(e, &response, colour)))
+    }).await
+}
@@ -0,0 +1,69 @@
+//! Runs the commands themselves against every backend, with a fake guild standing in for
+//! Discord. See `common` for running the DynamoDB ones.
+
+mod common;
+
+use leadershipdiscordbot_rs::commands::{points, store, Reply, ERROR_COLOUR};
+use leadershipdiscordbot_rs::commands::fake::{FakeGuild, args, embed, ADMIN, MEMBER};
+use leadershipdiscordbot_rs::storage::Storage;
+
+/// givepoints and givegems, then getpoints
+async fn points_and_gems_are_given_and_shown<S: Storage>(guild: FakeGuild<S>) {
+    let admin = guild.as_user(ADMIN);
+    assert_eq!(points::givepoints(&guild.as_user(MEMBER), &args(&format!("<@{}> 10", MEMBER))).await.unwrap(), Reply::Nothing);
+
+    let response = embed(points::givepoints(&admin, &args(&format!("<@{}> 10 \"Ran a workshop\"", MEMBER))).await.unwrap());
+    assert_eq!(response.title, "Given points!");
+    let response = embed(points::givegems(&admin, &args(&format!("<@{}> 5 Hackathon", MEMBER))).await.unwrap());
+    assert_eq!(response.description, "10 :star:\n5 :gem:");
+
+    let response = embed(points::getpoints(&guild.as_user(MEMBER), &[]).await.unwrap());
+    assert_eq!((response.title.as_str(), response.description.as_str()), ("member's points", "10 :star:\n5 :gem:"));
+    assert_eq!(guild.storage.get_entries(&MEMBER.to_string()).await.unwrap().len(), 2);
+}
+
+/// addproduct, buy until it's sold out, then delproduct
+async fn products_are_bought_until_sold_out_or_deleted<S: Storage>(guild: FakeGuild<S>) {
+    let admin = guild.as_user(ADMIN);
+    let member = guild.as_user(MEMBER);
+    let response = embed(store::addproduct(&admin, &args("hoodie Hoodie Warm 50 1")).await.unwrap());
+    assert_eq!(response.description, "`hoodie`: **Hoodie** (50 :gem:, 1 left)\nWarm");
+    store::addproduct(&admin, &args("sticker Sticker Shiny 5 10")).await.unwrap();
+
+    assert_eq!(embed(store::buy(&member, &args("hoodie")).await.unwrap()).title, "You can't afford that!");
+    points::givegems(&admin, &args(&format!("{} 110", MEMBER))).await.unwrap();
+    let response = embed(store::buy(&member, &args("hoodie")).await.unwrap());
+    assert_eq!(response.description, "You just purchased a Hoodie\nYou have 60 :gem: left");
+    assert_eq!(embed(store::buy(&member, &args("hoodie")).await.unwrap()).title, "Out of stock");
+
+    assert_eq!(store::delproduct(&member, &args("sticker")).await.unwrap(), Reply::Nothing);
+    assert_eq!(embed(store::delproduct(&admin, &args("sticker")).await.unwrap()).description, "Deleted product sticker");
+    let response = embed(store::buy(&member, &args("sticker")).await.unwrap());
+    assert_eq!((response.title.as_str(), response.colour), ("Cannot find product", Some(ERROR_COLOUR)));
+    assert_eq!(guild.storage.get_profile(&MEMBER.to_string()).await.unwrap().credits, 60);
+}
+
+/// Each scenario gets a fake guild on fresh storage from `new_storage`.
+macro_rules! command_tests {
+    ($module:ident, $new_storage:expr $(, #[$attr:meta])*) => {
+        mod $module {
+            use super::*;
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn points_and_gems_are_given_and_shown() {
+                super::points_and_gems_are_given_and_shown(FakeGuild::with_storage($new_storage.await)).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn products_are_bought_until_sold_out_or_deleted() {
+                super::products_are_bought_until_sold_out_or_deleted(FakeGuild::with_storage($new_storage.await)).await;
+            }
+        }
+    };
+}
+
+command_tests!(memory_storage, common::memory());
+command_tests!(sqlite_storage, common::sqlite());
+command_tests!(dynamodb_storage, common::dynamodb(), #[ignore = "needs DynamoDB Local, see common"]);
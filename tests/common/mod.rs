@@ -0,0 +1,44 @@
+//! Fresh storage for each test, on each backend. The DynamoDB tests are ignored unless asked
+//! for, and need `DYNAMODB_ENDPOINT` pointing at DynamoDB Local (any AWS credentials will do):
+//!
+//!     docker run -p 8000:8000 amazon/dynamodb-local   # or: moto_server -p 8000
+//!     DYNAMODB_ENDPOINT=http://localhost:8000 AWS_ACCESS_KEY_ID=x AWS_SECRET_ACCESS_KEY=x cargo test -- --include-ignored
+//!
+//! moto doesn't isolate transactions from each other, so `limits_hold_when_buying_at_once`
+//! can fail against it; DynamoDB Local runs it properly.
+
+use std::env;
+
+use rusoto_core::Region;
+use uuid::Uuid;
+
+use leadershipdiscordbot_rs::config::TablesConfig;
+use leadershipdiscordbot_rs::storage::{MemoryStorage, SqliteStorage, DynamoStorage};
+
+pub async fn memory() -> MemoryStorage {
+    MemoryStorage::new()
+}
+
+pub async fn sqlite() -> SqliteStorage {
+    SqliteStorage::open(":memory:").unwrap()
+}
+
+/// Fresh tables on DynamoDB Local.
+pub async fn dynamodb() -> DynamoStorage {
+    let endpoint = env::var("DYNAMODB_ENDPOINT").expect("DYNAMODB_ENDPOINT should point at DynamoDB Local to run the DynamoDB tests");
+    let run = Uuid::new_v4().to_simple().to_string();
+    let tables = TablesConfig {
+        profiles: format!("TPCMemberPoints-{}", run),
+        store: format!("TPCStore-{}", run),
+        purchases: format!("TPCPurchases-{}", run),
+        ledger: format!("TPCLedger-{}", run),
+        codes: format!("TPCCodes-{}", run),
+        claims: format!("TPCClaims-{}", run),
+        events: format!("TPCEvents-{}", run),
+        event_codes: format!("TPCEventCodes-{}", run),
+        attendance: format!("TPCAttendance-{}", run)
+    };
+    let storage = DynamoStorage::new(Region::Custom { name: "us-east-1".to_string(), endpoint }, tables);
+    storage.create_tables().await.unwrap();
+    storage
+}
@@ -0,0 +1,583 @@
+//! Runs what each command does to the data layer against every backend, without
+//! Discord or AWS. See `common` for running the DynamoDB ones.
+
+mod common;
+
+use uuid::Uuid;
+
+use leadershipdiscordbot_rs::storage::{self, Storage, Attendance, Claim, ClaimStatus, Event, EventOutcome, Product, Profile, ProductChanges, Purchase, PurchaseOutcome, PurchaseStatus, RefundOutcome, TransferOutcome, LedgerEntry};
+
+const ADMIN: &str = "100000000000000001";
+const MEMBER: &str = "100000000000000002";
+const OTHER_MEMBER: &str = "100000000000000003";
+
+fn entry(discord_id: &str, points: i64, credits: i64, reason: &str) -> LedgerEntry {
+    LedgerEntry {
+        id: Uuid::new_v4().to_string(),
+        actor_id: ADMIN.to_string(),
+        discord_id: discord_id.to_string(),
+        points,
+        credits,
+        reason: reason.to_string(),
+        timestamp: storage::now(),
+        message_id: "1".to_string()
+    }
+}
+
+fn product(key: &str, price: i64, quantity: i64) -> Product {
+    Product {
+        key: key.to_string(),
+        name: format!("{} name", key),
+        description: format!("{} description", key),
+        price,
+        quantity,
+        member_limit: 0,
+        unlimited: false,
+        digital: false
+    }
+}
+
+fn purchase(product_key: &str, discord_id: &str) -> Purchase {
+    Purchase {
+        id: Uuid::new_v4().to_string(),
+        product_key: product_key.to_string(),
+        discord_id: discord_id.to_string(),
+        price: 0,
+        timestamp: storage::now(),
+        status: PurchaseStatus::Pending,
+        code: None
+    }
+}
+
+/// getpoints on someone who has never been given anything
+async fn unknown_member_has_empty_profile(storage: &dyn Storage) {
+    let profile = storage.get_profile(MEMBER).await.unwrap();
+    assert_eq!((profile.points, profile.credits), (0, 0));
+}
+
+/// givepoints then givegems, then getpoints
+async fn awards_add_up(storage: &dyn Storage) {
+    let profile = storage.record_entry(entry(MEMBER, 10, 0, "Workshop")).await.unwrap();
+    assert_eq!((profile.points, profile.credits), (10, 0));
+
+    let profile = storage.record_entry(entry(MEMBER, 0, 5, "Hackathon")).await.unwrap();
+    assert_eq!((profile.points, profile.credits), (10, 5));
+
+    let profile = storage.get_profile(MEMBER).await.unwrap();
+    assert_eq!((profile.points, profile.credits), (10, 5));
+
+    let entries = storage.get_entries(MEMBER).await.unwrap();
+    assert_eq!(entries.len(), 2);
+    let balance = storage::balance(&entries);
+    assert_eq!((balance.points, balance.credits), (10, 5));
+
+    let profiles = storage.get_profiles().await.unwrap();
+    assert!(profiles.iter().any(|(user_id, profile)| user_id == MEMBER && profile.points == 10));
+}
+
+/// Opening balances only go into empty ledgers, for the balance the member actually has
+async fn ledgers_only_open_once(storage: &dyn Storage) {
+    storage.record_entry(entry(MEMBER, 10, 0, "Workshop")).await.unwrap();
+    assert!(!storage.open_ledger(storage::opening_entry(MEMBER, &Profile { points: 10, credits: 0 })).await.unwrap());
+    assert!(!storage.open_ledger(storage::opening_entry(OTHER_MEMBER, &Profile { points: 5, credits: 0 })).await.unwrap());
+    assert_eq!(storage::open_ledgers(storage).await.unwrap(), 0);
+
+    assert_eq!(storage.get_entries(MEMBER).await.unwrap().len(), 1);
+    assert!(storage.get_entries(OTHER_MEMBER).await.unwrap().is_empty());
+    assert_eq!(storage.get_profile(MEMBER).await.unwrap().points, 10);
+}
+
+/// addproduct, store and delproduct
+async fn products_can_be_added_and_deleted(storage: &dyn Storage) {
+    storage.put_product(product("sticker", 5, 10)).await.unwrap();
+    storage.put_product(product("hoodie", 50, 2)).await.unwrap();
+
+    let mut keys: Vec<String> = storage.get_store().await.unwrap().into_iter().map(|product| product.key).collect();
+    keys.sort();
+    assert_eq!(keys, vec!["hoodie", "sticker"]);
+
+    let sticker = storage.get_product("sticker").await.unwrap().unwrap();
+    assert_eq!((sticker.name.as_str(), sticker.price, sticker.quantity), ("sticker name", 5, 10));
+
+    storage.delete_product("sticker").await.unwrap();
+    assert!(storage.get_product("sticker").await.unwrap().is_none());
+    assert_eq!(storage.get_store().await.unwrap().len(), 1);
+}
+
+/// editproduct and restock
+async fn products_can_be_edited_and_restocked(storage: &dyn Storage) {
+    storage.put_product(product("hoodie", 50, 1)).await.unwrap();
+
+    let changes = ProductChanges { name: Some("Club hoodie".to_string()), price: Some(40), ..Default::default() };
+    let edited = storage.edit_product("hoodie", changes).await.unwrap().unwrap();
+    assert_eq!((edited.name.as_str(), edited.description.as_str(), edited.price, edited.quantity), ("Club hoodie", "hoodie description", 40, 1));
+    assert!(storage.edit_product("sticker", ProductChanges { price: Some(1), ..Default::default() }).await.unwrap().is_none());
+
+    assert_eq!(storage.restock("hoodie", 4).await.unwrap().unwrap().quantity, 5);
+    assert_eq!(storage.get_product("hoodie").await.unwrap().unwrap().quantity, 5);
+    assert!(storage.restock("sticker", 4).await.unwrap().is_none());
+    assert!(storage.get_product("sticker").await.unwrap().is_none());
+}
+
+/// buy, including every way it can be refused
+async fn buying_charges_gems_and_takes_stock(storage: &dyn Storage) {
+    storage.put_product(product("hoodie", 50, 1)).await.unwrap();
+
+    match storage.buy(purchase("hoodie", MEMBER), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap() {
+        PurchaseOutcome::CannotAfford { credits, .. } => assert_eq!(credits, 0),
+        other => panic!("Expected CannotAfford, got {:?}", other)
+    }
+
+    storage.record_entry(entry(MEMBER, 0, 60, "Hackathon")).await.unwrap();
+    match storage.buy(purchase("hoodie", MEMBER), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap() {
+        PurchaseOutcome::Purchased { product, profile, .. } => {
+            assert_eq!(product.quantity, 0);
+            assert_eq!(profile.credits, 10);
+        },
+        other => panic!("Expected Purchased, got {:?}", other)
+    }
+    assert_eq!(storage.get_product("hoodie").await.unwrap().unwrap().quantity, 0);
+
+    storage.record_entry(entry(MEMBER, 0, 100, "Hackathon")).await.unwrap();
+    match storage.buy(purchase("hoodie", MEMBER), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap() {
+        PurchaseOutcome::OutOfStock(product) => assert_eq!(product.key, "hoodie"),
+        other => panic!("Expected OutOfStock, got {:?}", other)
+    }
+
+    match storage.buy(purchase("nothing", MEMBER), entry(MEMBER, 0, 0, "Bought nothing")).await.unwrap() {
+        PurchaseOutcome::NoSuchProduct => {},
+        other => panic!("Expected NoSuchProduct, got {:?}", other)
+    }
+
+    // Only the successful purchase is in the ledger, and it still adds up
+    let entries = storage.get_entries(MEMBER).await.unwrap();
+    assert_eq!(entries.iter().filter(|entry| entry.credits == -50).count(), 1);
+    assert_eq!(storage::balance(&entries).credits, storage.get_profile(MEMBER).await.unwrap().credits);
+}
+
+/// orders, then fulfil and cancel
+async fn orders_can_be_settled_once(storage: &dyn Storage) {
+    storage.put_product(product("hoodie", 10, 5)).await.unwrap();
+    storage.record_entry(entry(MEMBER, 0, 20, "Hackathon")).await.unwrap();
+    let first = purchase("hoodie", MEMBER);
+    let second = purchase("hoodie", MEMBER);
+    storage.buy(first.clone(), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap();
+    storage.buy(second.clone(), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap();
+    assert_eq!(storage.get_purchases_by_status(PurchaseStatus::Pending).await.unwrap().len(), 2);
+
+    let fulfilled = storage.update_purchase_status(&first.id, PurchaseStatus::Pending, PurchaseStatus::Fulfilled).await.unwrap();
+    assert_eq!(fulfilled.unwrap().status, PurchaseStatus::Fulfilled);
+    assert!(storage.update_purchase_status(&first.id, PurchaseStatus::Pending, PurchaseStatus::Cancelled).await.unwrap().is_none());
+    assert!(storage.update_purchase_status("nothing", PurchaseStatus::Pending, PurchaseStatus::Cancelled).await.unwrap().is_none());
+    storage.update_purchase_status(&second.id, PurchaseStatus::Pending, PurchaseStatus::Cancelled).await.unwrap();
+
+    assert!(storage.get_purchases_by_status(PurchaseStatus::Pending).await.unwrap().is_empty());
+    assert_eq!(storage.get_purchase(&first.id).await.unwrap().unwrap().status, PurchaseStatus::Fulfilled);
+    assert_eq!(storage.get_purchase(&second.id).await.unwrap().unwrap().status, PurchaseStatus::Cancelled);
+    assert!(storage.get_purchase("nothing").await.unwrap().is_none());
+
+    let bought = storage.get_purchases_by_member(MEMBER).await.unwrap();
+    assert_eq!(bought.len(), 2);
+    assert!(bought.iter().all(|purchase| purchase.price == 10 && purchase.timestamp == first.timestamp));
+    assert!(storage.get_purchases_by_member(OTHER_MEMBER).await.unwrap().is_empty());
+}
+
+/// refund, including refunding twice, refunding something no longer in the store and
+/// refunding a purchase from before prices were recorded
+async fn refunds_restore_gems_and_stock(storage: &dyn Storage) {
+    storage.put_product(product("hoodie", 30, 2)).await.unwrap();
+    storage.put_product(product("sticker", 5, 2)).await.unwrap();
+    storage.record_entry(entry(MEMBER, 0, 40, "Hackathon")).await.unwrap();
+    let hoodie = purchase("hoodie", MEMBER);
+    let sticker = purchase("sticker", MEMBER);
+    storage.buy(hoodie.clone(), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap();
+    storage.buy(sticker.clone(), entry(MEMBER, 0, 0, "Bought sticker")).await.unwrap();
+    assert_eq!(storage.get_purchase(&hoodie.id).await.unwrap().unwrap().price, 30);
+
+    match storage.refund(&hoodie.id, entry(MEMBER, 0, 0, "Refunded hoodie")).await.unwrap() {
+        RefundOutcome::Refunded { purchase, profile } => {
+            assert_eq!((purchase.status, purchase.price), (PurchaseStatus::Refunded, 30));
+            assert_eq!(profile.credits, 35);
+        },
+        other => panic!("Expected Refunded, got {:?}", other)
+    }
+    assert_eq!(storage.get_product("hoodie").await.unwrap().unwrap().quantity, 2);
+    match storage.refund(&hoodie.id, entry(MEMBER, 0, 0, "Refunded hoodie")).await.unwrap() {
+        RefundOutcome::AlreadyClosed(purchase) => assert_eq!(purchase.id, hoodie.id),
+        other => panic!("Expected AlreadyClosed, got {:?}", other)
+    }
+    match storage.refund("nothing", entry(MEMBER, 0, 0, "Refunded nothing")).await.unwrap() {
+        RefundOutcome::NoSuchPurchase => {},
+        other => panic!("Expected NoSuchPurchase, got {:?}", other)
+    }
+
+    // Deleted products aren't brought back just to restock them
+    storage.delete_product("sticker").await.unwrap();
+    storage.refund(&sticker.id, entry(MEMBER, 0, 0, "Refunded sticker")).await.unwrap();
+    assert!(storage.get_product("sticker").await.unwrap().is_none());
+
+    // With neither a price nor a time, it's from before prices were recorded
+    storage.put_product(product("badge", 0, 1)).await.unwrap();
+    let badge = Purchase { timestamp: 0, ..purchase("badge", MEMBER) };
+    storage.buy(badge.clone(), entry(MEMBER, 0, 0, "Bought badge")).await.unwrap();
+    match storage.refund(&badge.id, entry(MEMBER, 0, 0, "Refunded badge")).await.unwrap() {
+        RefundOutcome::PriceUnknown(purchase) => assert_eq!(purchase.status, PurchaseStatus::Pending),
+        other => panic!("Expected PriceUnknown, got {:?}", other)
+    }
+    assert_eq!(storage.get_product("badge").await.unwrap().unwrap().quantity, 0);
+
+    let entries = storage.get_entries(MEMBER).await.unwrap();
+    assert_eq!(entries.iter().filter(|entry| entry.reason.starts_with("Refunded")).count(), 2);
+    assert_eq!(storage::balance(&entries).credits, 40);
+    assert_eq!(storage.get_profile(MEMBER).await.unwrap().credits, 40);
+}
+
+/// cancel, which pays back pending orders like a refund but nothing else
+async fn cancelling_restores_gems_and_stock(storage: &dyn Storage) {
+    storage.put_product(product("hoodie", 30, 2)).await.unwrap();
+    storage.record_entry(entry(MEMBER, 0, 60, "Hackathon")).await.unwrap();
+    let pending = purchase("hoodie", MEMBER);
+    let fulfilled = purchase("hoodie", MEMBER);
+    storage.buy(pending.clone(), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap();
+    storage.buy(fulfilled.clone(), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap();
+    storage.update_purchase_status(&fulfilled.id, PurchaseStatus::Pending, PurchaseStatus::Fulfilled).await.unwrap();
+
+    match storage.cancel(&pending.id, entry(MEMBER, 0, 0, "Cancelled hoodie")).await.unwrap() {
+        RefundOutcome::Refunded { purchase, profile } => {
+            assert_eq!(purchase.status, PurchaseStatus::Cancelled);
+            assert_eq!(profile.credits, 30);
+        },
+        other => panic!("Expected Refunded, got {:?}", other)
+    }
+    assert_eq!(storage.get_product("hoodie").await.unwrap().unwrap().quantity, 1);
+
+    // Neither can be paid back a second time, and fulfilled orders need refunding instead
+    for (id, status) in [(&pending.id, PurchaseStatus::Cancelled), (&fulfilled.id, PurchaseStatus::Fulfilled)] {
+        match storage.cancel(id, entry(MEMBER, 0, 0, "Cancelled hoodie")).await.unwrap() {
+            RefundOutcome::AlreadyClosed(purchase) => assert_eq!(purchase.status, status),
+            other => panic!("Expected AlreadyClosed, got {:?}", other)
+        }
+    }
+    match storage.refund(&pending.id, entry(MEMBER, 0, 0, "Refunded hoodie")).await.unwrap() {
+        RefundOutcome::AlreadyClosed(purchase) => assert_eq!(purchase.status, PurchaseStatus::Cancelled),
+        other => panic!("Expected AlreadyClosed, got {:?}", other)
+    }
+
+    let entries = storage.get_entries(MEMBER).await.unwrap();
+    assert_eq!(storage::balance(&entries).credits, 30);
+    assert_eq!(storage.get_profile(MEMBER).await.unwrap().credits, 30);
+    assert_eq!(storage.get_product("hoodie").await.unwrap().unwrap().quantity, 1);
+}
+
+/// buy on products with a per-member limit or unlimited stock
+async fn limits_and_unlimited_stock_apply(storage: &dyn Storage) {
+    storage.put_product(Product { member_limit: 1, ..product("hoodie", 10, 5) }).await.unwrap();
+    storage.put_product(Product { unlimited: true, ..product("perk", 10, 0) }).await.unwrap();
+    storage.record_entry(entry(MEMBER, 0, 100, "Hackathon")).await.unwrap();
+
+    let hoodie = purchase("hoodie", MEMBER);
+    storage.buy(hoodie.clone(), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap();
+    match storage.buy(purchase("hoodie", MEMBER), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap() {
+        PurchaseOutcome::LimitReached(product) => assert_eq!(product.member_limit, 1),
+        other => panic!("Expected LimitReached, got {:?}", other)
+    }
+    // Refunds don't count towards the limit
+    storage.refund(&hoodie.id, entry(MEMBER, 0, 0, "Refunded hoodie")).await.unwrap();
+    match storage.buy(purchase("hoodie", MEMBER), entry(MEMBER, 0, 0, "Bought hoodie")).await.unwrap() {
+        PurchaseOutcome::Purchased { .. } => {},
+        other => panic!("Expected Purchased, got {:?}", other)
+    }
+
+    for _ in 0..2 {
+        match storage.buy(purchase("perk", MEMBER), entry(MEMBER, 0, 0, "Bought perk")).await.unwrap() {
+            PurchaseOutcome::Purchased { product, .. } => assert_eq!(product.quantity, 0),
+            other => panic!("Expected Purchased, got {:?}", other)
+        }
+    }
+    assert_eq!(storage.get_product("perk").await.unwrap().unwrap().quantity, 0);
+    assert_eq!(storage.get_profile(MEMBER).await.unwrap().credits, 70);
+
+    let changes = ProductChanges { member_limit: Some(0), unlimited: Some(false), ..Default::default() };
+    let edited = storage.edit_product("perk", changes).await.unwrap().unwrap();
+    assert_eq!((edited.member_limit, edited.unlimited), (0, false));
+}
+
+/// buy called twice at the same moment on a product with a per-member limit
+async fn limits_hold_when_buying_at_once(storage: &dyn Storage) {
+    storage.put_product(Product { member_limit: 1, ..product("hoodie", 10, 5) }).await.unwrap();
+    storage.record_entry(entry(MEMBER, 0, 100, "Hackathon")).await.unwrap();
+
+    let (first, second) = tokio::join!(
+        storage.buy(purchase("hoodie", MEMBER), entry(MEMBER, 0, 0, "Bought hoodie")),
+        storage.buy(purchase("hoodie", MEMBER), entry(MEMBER, 0, 0, "Bought hoodie"))
+    );
+    let bought = [first.unwrap(), second.unwrap()].iter()
+        .filter(|outcome| matches!(outcome, PurchaseOutcome::Purchased { .. }))
+        .count();
+    assert_eq!(bought, 1);
+    assert_eq!(storage.get_profile(MEMBER).await.unwrap().credits, 90);
+    assert_eq!(storage.get_product("hoodie").await.unwrap().unwrap().quantity, 4);
+}
+
+/// delproduct on a digital product with codes left, then adding it again with new codes
+async fn deleted_products_take_their_codes_with_them(storage: &dyn Storage) {
+    storage.put_product(product("voucher", 10, 0)).await.unwrap();
+    storage.add_codes("voucher", &["OLD-1".to_string(), "OLD-2".to_string()]).await.unwrap();
+    storage.delete_product("voucher").await.unwrap();
+
+    storage.put_product(product("voucher", 10, 0)).await.unwrap();
+    let voucher = storage.add_codes("voucher", &["NEW-1".to_string()]).await.unwrap().unwrap();
+    assert_eq!(voucher.quantity, 1);
+    storage.record_entry(entry(MEMBER, 0, 100, "Hackathon")).await.unwrap();
+    match storage.buy(purchase("voucher", MEMBER), entry(MEMBER, 0, 0, "Bought voucher")).await.unwrap() {
+        PurchaseOutcome::Purchased { code, .. } => assert_eq!(code.as_deref(), Some("NEW-1")),
+        other => panic!("Expected Purchased, got {:?}", other)
+    }
+    match storage.buy(purchase("voucher", MEMBER), entry(MEMBER, 0, 0, "Bought voucher")).await.unwrap() {
+        PurchaseOutcome::OutOfStock(_) => {},
+        other => panic!("Expected OutOfStock, got {:?}", other)
+    }
+}
+
+/// addcodes, then buying and refunding a digital product
+async fn digital_products_hand_out_each_code_once(storage: &dyn Storage) {
+    assert!(storage.add_codes("voucher", &["A".to_string()]).await.unwrap().is_none());
+    storage.put_product(product("voucher", 10, 0)).await.unwrap();
+    let codes = vec!["A".to_string(), "B".to_string(), "A".to_string()];
+    let voucher = storage.add_codes("voucher", &codes).await.unwrap().unwrap();
+    assert_eq!((voucher.digital, voucher.quantity), (true, 2));
+    storage.record_entry(entry(MEMBER, 0, 100, "Hackathon")).await.unwrap();
+
+    let mut handed_out = Vec::new();
+    for _ in 0..2 {
+        let bought = purchase("voucher", MEMBER);
+        match storage.buy(bought.clone(), entry(MEMBER, 0, 0, "Bought voucher")).await.unwrap() {
+            PurchaseOutcome::Purchased { code: Some(code), .. } => {
+                assert_eq!(storage.get_purchase(&bought.id).await.unwrap().unwrap().code, Some(code.clone()));
+                handed_out.push(code);
+            },
+            other => panic!("Expected a code, got {:?}", other)
+        }
+    }
+    handed_out.sort();
+    assert_eq!(handed_out, vec!["A", "B"]);
+    match storage.buy(purchase("voucher", MEMBER), entry(MEMBER, 0, 0, "Bought voucher")).await.unwrap() {
+        PurchaseOutcome::OutOfStock(_) => {},
+        other => panic!("Expected OutOfStock, got {:?}", other)
+    }
+
+    // A refunded code has been seen, so it doesn't go back in stock
+    let bought = storage.get_purchases_by_member(MEMBER).await.unwrap();
+    storage.refund(&bought[0].id, entry(MEMBER, 0, 0, "Refunded voucher")).await.unwrap();
+    assert_eq!(storage.get_product("voucher").await.unwrap().unwrap().quantity, 0);
+}
+
+/// transfer between members
+async fn transfers_move_gems(storage: &dyn Storage) {
+    storage.record_entry(entry(MEMBER, 0, 20, "Hackathon")).await.unwrap();
+
+    match storage.transfer(entry(MEMBER, 0, -30, "Sent"), entry(OTHER_MEMBER, 0, 30, "Received")).await.unwrap() {
+        TransferOutcome::InsufficientCredits(credits) => assert_eq!(credits, 20),
+        other => panic!("Expected InsufficientCredits, got {:?}", other)
+    }
+
+    match storage.transfer(entry(MEMBER, 0, -15, "Sent"), entry(OTHER_MEMBER, 0, 15, "Received")).await.unwrap() {
+        TransferOutcome::Transferred(profile) => assert_eq!(profile.credits, 5),
+        other => panic!("Expected Transferred, got {:?}", other)
+    }
+    assert_eq!(storage.get_profile(OTHER_MEMBER).await.unwrap().credits, 15);
+    assert_eq!(storage.get_entries(OTHER_MEMBER).await.unwrap().len(), 1);
+}
+
+/// claim, then approve or reject it once
+async fn claims_are_reviewed_once(storage: &dyn Storage) {
+    let claim = Claim {
+        id: Uuid::new_v4().to_string(),
+        activity_id: "reinvent".to_string(),
+        discord_id: MEMBER.to_string(),
+        evidence: "https://example.com/photo.jpg".to_string(),
+        timestamp: storage::now(),
+        status: ClaimStatus::Pending,
+        reviewer_id: None
+    };
+    storage.put_claim(claim.clone()).await.unwrap();
+    assert_eq!(storage.get_claim(&claim.id).await.unwrap(), Some(claim.clone()));
+    assert_eq!(storage.get_claims_by_member(MEMBER).await.unwrap(), vec![claim.clone()]);
+    assert!(storage.get_claims_by_member(OTHER_MEMBER).await.unwrap().is_empty());
+
+    let approved = storage.review_claim(&claim.id, ClaimStatus::Approved, ADMIN).await.unwrap().unwrap();
+    assert_eq!((approved.status, approved.reviewer_id.as_deref()), (ClaimStatus::Approved, Some(ADMIN)));
+    assert!(storage.review_claim(&claim.id, ClaimStatus::Rejected, ADMIN).await.unwrap().is_none());
+    assert!(storage.review_claim("no-such-claim", ClaimStatus::Approved, ADMIN).await.unwrap().is_none());
+    assert_eq!(storage.get_claim(&claim.id).await.unwrap().unwrap().status, ClaimStatus::Approved);
+}
+
+/// claim, then approve it twice, awarding it once
+async fn approving_a_claim_awards_it_once(storage: &dyn Storage) {
+    let claim = Claim {
+        id: Uuid::new_v4().to_string(),
+        activity_id: "aws-cert".to_string(),
+        discord_id: MEMBER.to_string(),
+        evidence: "https://example.com/cert.pdf".to_string(),
+        timestamp: storage::now(),
+        status: ClaimStatus::Pending,
+        reviewer_id: None
+    };
+    storage.put_claim(claim.clone()).await.unwrap();
+
+    let (approved, profile) = storage.approve_claim(&claim.id, ADMIN, entry(MEMBER, 100, 5, "Claimed")).await.unwrap().unwrap();
+    assert_eq!((approved.status, approved.reviewer_id.as_deref()), (ClaimStatus::Approved, Some(ADMIN)));
+    assert_eq!((profile.points, profile.credits), (100, 5));
+    assert!(storage.approve_claim(&claim.id, ADMIN, entry(MEMBER, 100, 5, "Claimed")).await.unwrap().is_none());
+    assert!(storage.approve_claim("no-such-claim", ADMIN, entry(MEMBER, 100, 5, "Claimed")).await.unwrap().is_none());
+    assert_eq!(storage.get_profile(MEMBER).await.unwrap().points, 100);
+    assert_eq!(storage.get_entries(MEMBER).await.unwrap().len(), 1);
+    assert_eq!(storage.get_claim(&claim.id).await.unwrap().unwrap().status, ClaimStatus::Approved);
+}
+
+/// addevent, then checkin twice and attendance
+async fn members_check_in_to_events_once(storage: &dyn Storage) {
+    let event = Event {
+        id: "rust".to_string(),
+        name: "Rust workshop".to_string(),
+        code: "FERRIS".to_string(),
+        points: 15,
+        expires: storage::now() + 3600,
+        timestamp: storage::now()
+    };
+    assert!(matches!(storage.add_event(event.clone()).await.unwrap(), EventOutcome::Added));
+    assert!(matches!(storage.add_event(Event { code: "OTHER".to_string(), ..event.clone() }).await.unwrap(), EventOutcome::IdTaken));
+    match storage.add_event(Event { id: "rust2".to_string(), ..event.clone() }).await.unwrap() {
+        EventOutcome::CodeInUse(clash) => assert_eq!(clash, event),
+        other => panic!("Expected CodeInUse, got {:?}", other)
+    }
+    assert_eq!(storage.get_event("rust").await.unwrap(), Some(event.clone()));
+    assert_eq!(storage.get_events().await.unwrap(), vec![event.clone()]);
+    // Once it closes, the code is free for another event
+    let later = Event { id: "rust2".to_string(), timestamp: event.expires, expires: event.expires + 3600, ..event.clone() };
+    assert!(matches!(storage.add_event(later).await.unwrap(), EventOutcome::Added));
+
+    let attendance = Attendance { event_id: "rust".to_string(), discord_id: MEMBER.to_string(), timestamp: storage::now() };
+    let profile = storage.check_in(attendance.clone(), entry(MEMBER, 15, 0, "Checked in")).await.unwrap();
+    assert_eq!(profile.map(|profile| profile.points), Some(15));
+    assert!(storage.check_in(attendance.clone(), entry(MEMBER, 15, 0, "Checked in")).await.unwrap().is_none());
+    assert_eq!(storage.get_profile(MEMBER).await.unwrap().points, 15);
+    assert_eq!(storage.get_entries(MEMBER).await.unwrap().len(), 1);
+    assert_eq!(storage.get_attendance("rust").await.unwrap(), vec![attendance]);
+    assert!(storage.get_attendance("other").await.unwrap().is_empty());
+}
+
+/// Each scenario gets a fresh backend from `new_storage`.
+macro_rules! storage_tests {
+    ($module:ident, $new_storage:expr $(, #[$attr:meta])*) => {
+        mod $module {
+            use super::*;
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn unknown_member_has_empty_profile() {
+                super::unknown_member_has_empty_profile(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn awards_add_up() {
+                super::awards_add_up(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn ledgers_only_open_once() {
+                super::ledgers_only_open_once(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn products_can_be_added_and_deleted() {
+                super::products_can_be_added_and_deleted(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn products_can_be_edited_and_restocked() {
+                super::products_can_be_edited_and_restocked(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn buying_charges_gems_and_takes_stock() {
+                super::buying_charges_gems_and_takes_stock(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn orders_can_be_settled_once() {
+                super::orders_can_be_settled_once(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn refunds_restore_gems_and_stock() {
+                super::refunds_restore_gems_and_stock(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn cancelling_restores_gems_and_stock() {
+                super::cancelling_restores_gems_and_stock(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn limits_and_unlimited_stock_apply() {
+                super::limits_and_unlimited_stock_apply(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn limits_hold_when_buying_at_once() {
+                super::limits_hold_when_buying_at_once(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn deleted_products_take_their_codes_with_them() {
+                super::deleted_products_take_their_codes_with_them(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn digital_products_hand_out_each_code_once() {
+                super::digital_products_hand_out_each_code_once(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn claims_are_reviewed_once() {
+                super::claims_are_reviewed_once(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn approving_a_claim_awards_it_once() {
+                super::approving_a_claim_awards_it_once(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn members_check_in_to_events_once() {
+                super::members_check_in_to_events_once(&$new_storage.await).await;
+            }
+
+            #[tokio::test]
+            $(#[$attr])*
+            async fn transfers_move_gems() {
+                super::transfers_move_gems(&$new_storage.await).await;
+            }
+        }
+    };
+}
+
+storage_tests!(memory_storage, common::memory());
+storage_tests!(sqlite_storage, common::sqlite());
+storage_tests!(dynamodb_storage, common::dynamodb(), #[ignore = "needs DynamoDB Local, see common"]);